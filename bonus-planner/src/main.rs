@@ -0,0 +1,146 @@
+extern crate lib;
+
+use lib::bonus::{gain_estimates_from_json, plan, BonusEdge, GainEstimate, UnlockGraph};
+use lib::data::{Bonus, BonusType, Pose, Problem};
+use lib::paths::DataDir;
+use lib::score::{score, score_potential};
+use lib::validation::{dislike, is_bonus_collected, validate};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// 見積もりが与えられていない bonus は、使う問題の残りの得点のうちこれだけ取れると仮定する
+fn default_gain_rate(bonus: BonusType) -> f64 {
+    match bonus {
+        BonusType::Globalist => 0.3,
+        BonusType::Superflex => 0.2,
+        BonusType::Wallhack => 0.2,
+        BonusType::BreakALeg => 0.3,
+    }
+}
+
+// まだ取れていない bonus を取りに行くと、取る問題の得点のうちこれだけ失うと仮定する
+const COLLECT_COST_RATE: f64 = 0.05;
+
+fn main() {
+    let max_id = 132;
+    let data = DataDir::from_args(&env::args().collect::<Vec<String>>());
+    let gains_filepath = data.file("bonus_gains.json");
+
+    let mut problems = vec![];
+    // valid な best がある問題の解
+    let mut poses: HashMap<usize, Pose> = HashMap::new();
+    for id in 1..=max_id {
+        if !data.has_problem(id) {
+            continue;
+        }
+        let problem = Problem::from_file(data.problem(id).as_str());
+        if data.has_best(id) {
+            let pose = Pose::from_file(data.best(id).as_str());
+            if validate(&problem, &pose).is_valid() {
+                poses.insert(id, pose);
+            }
+        }
+        problems.push((id, problem));
+    }
+    let problem_of = |id: usize| problems.iter().find(|(i, _)| *i == id).map(|(_, p)| p);
+    let current_dislike = |id: usize| -> Option<f64> {
+        let problem = problem_of(id)?;
+        poses.get(&id).map(|pose| dislike(problem, pose))
+    };
+
+    let graph = UnlockGraph::new(&problems);
+    println!("{} problems, {} bonuses", problems.len(), graph.edges.len());
+
+    let estimates: Vec<GainEstimate> = if Path::new(gains_filepath.as_str()).exists() {
+        gain_estimates_from_json(
+            fs::read_to_string(gains_filepath.as_str())
+                .unwrap()
+                .as_str(),
+        )
+    } else {
+        println!("{} does not exist, use default estimates", gains_filepath);
+        vec![]
+    };
+
+    let gain = |edge: &BonusEdge| -> f64 {
+        if let Some(e) = estimates
+            .iter()
+            .find(|e| e.target == edge.target && e.bonus == edge.bonus)
+        {
+            return e.gain;
+        }
+        match problem_of(edge.target) {
+            Some(problem) => {
+                score_potential(problem, current_dislike(edge.target))
+                    * default_gain_rate(edge.bonus)
+            }
+            None => 0.0,
+        }
+    };
+    let cost = |edge: &BonusEdge| -> Option<f64> {
+        let problem = problem_of(edge.source)?;
+        let pose = poses.get(&edge.source)?;
+        if is_bonus_collected(
+            pose,
+            &Bonus {
+                bonus: edge.bonus,
+                problem: edge.target,
+                position: edge.position,
+            },
+        ) {
+            Some(0.0)
+        } else {
+            Some(score(problem, dislike(problem, pose), 0.0) * COLLECT_COST_RATE)
+        }
+    };
+
+    let actions = plan(&graph, gain, cost);
+
+    // 先に source で bonus を取り、その後 target で使う
+    println!("collect:");
+    for action in actions.iter().filter(|a| !a.collected) {
+        let e = &action.edge;
+        println!(
+            "  problem {}: put a vertex on {} for {} of problem {} (cost {:.0})",
+            e.source,
+            e.position.to_json(),
+            e.bonus.as_str(),
+            e.target,
+            action.cost
+        );
+    }
+    println!("use:");
+    for action in actions.iter() {
+        let e = &action.edge;
+        println!(
+            "  problem {}: solve with {} from problem {} (gain {:.0}{})",
+            e.target,
+            e.bonus.as_str(),
+            e.source,
+            action.gain,
+            if action.collected {
+                ", already collected"
+            } else {
+                ""
+            }
+        );
+    }
+    // target を解き直すとそこで取っていた bonus を失うことがあるので、取り直しが必要
+    for action in actions.iter() {
+        let target = action.edge.target;
+        if actions
+            .iter()
+            .any(|a| a.edge.source == target && a.edge.target != target)
+        {
+            println!(
+                "  note: problem {} is also a source, keep its bonus positions when re-solving",
+                target
+            );
+        }
+    }
+
+    let total = actions.iter().map(|a| a.net_gain()).sum::<f64>();
+    println!("expected total score change: {:+.0}", total);
+}
@@ -0,0 +1,472 @@
+extern crate lib;
+extern crate solver;
+
+use lib::args::Args;
+use lib::bonus::is_unlocked;
+use lib::client::{get_problem, submit_problem, ClientConfig};
+use lib::compare::{compare_poses, describe_violations};
+use lib::data::{Pose, Problem};
+use lib::paths::{create_parent, DataDir};
+use lib::render::{render_animation, render_comparison, render_svg};
+use lib::score::{score, score_potential};
+use lib::store::{read_seed, SolutionMeta, SolutionRecord, SolutionStore};
+use lib::trajectory::Trajectory;
+use lib::validation::{dislike, validate};
+use rayon::prelude::*;
+use solver::{
+    bonus_from_args, collect_best, default_seed, find_bonus_source, save_if_better, solve, solve2,
+    solve_with_bonus, Budget, CollectConfig, SolverConfig, FLAGS,
+};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const MAX_ID: usize = 132;
+
+const USAGE: &str = "usage: cli <command> [ids...] [options]
+
+commands:
+  fetch     download problems to <data>/in
+  solve     solve problems and update <data>/best (--timeout <ms>, --seed <seed>,
+            --out <dir> for poses that are not valid (default <data>/out),
+            --warm-start <file|best|none> starts from a pose (default none), --perturbation <k>
+            moves each vertex of it by up to k,
+            --placement searches orientations and positions for initial poses (not with --warm-start),
+            --relax builds initial poses by continuous relaxation,
+            --bonus <GLOBALIST|SUPERFLEX|WALLHACK> solves again with a bonus another problem gives,
+            --collect-bonuses pulls vertices toward bonus positions (--bonus-weight <w>, default 1) and
+            collects bonuses afterwards, letting the dislike grow by --tolerance <d> (default 0),
+            --trajectory <n> records the annealing every n iterations to <data>/debug/trajectory_{id}.txt,
+            --telemetry <ms> appends solver statistics to <data>/debug/telemetry_{id}.jsonl,
+            --telemetry-format <jsonl|csv>, --label <name> tags the rows with a config name)
+  validate  validate poses (--dir <dir>, default <data>/best)
+  score     print dislike and score of poses (--dir <dir>, default <data>/best)
+  render    draw problems and poses to <data>/debug/{id}.svg (--dir <dir>, default <data>/best)
+  replay    turn recorded trajectories into <data>/debug/trajectory_{id}.html, or SVG frames in
+            <data>/debug/trajectory_{id}/ with --format svg (--file <file>, --frames <n>, default 200)
+  compare   compare two poses of one problem (--a <file>, default <data>/best/{id}.json, --b <file>,
+            --svg <file> draws both poses overlaid)
+  solutions list solutions kept in <data>/solutions (--query best|unsubmitted|all, default best;
+            --import <dir> adds valid poses in <dir> that are not kept yet)
+  submit    submit valid poses in <data>/best and mark them submitted in <data>/solutions
+
+ids are numbers or ranges like 1-10, separated by spaces or commas (default: 1-132)
+<data> is given by --data <dir>, or $ICFPC_DATA_DIR (default ./data)
+fetch and submit read the API token from $ICFPC_API_TOKEN (and the URL from $ICFPC_API_URL,
+default https://poses.live), or from <data>/client.json: {\"url\": \"...\", \"token\": \"...\"}";
+
+fn load_problem(data: &DataDir, id: usize) -> Option<Problem> {
+    let filepath = data.problem(id);
+    if Path::new(filepath.as_str()).exists() {
+        Some(Problem::from_file(filepath.as_str()))
+    } else {
+        println!("problem {}: {} does not exist", id, filepath);
+        None
+    }
+}
+
+fn load_pose(dir: &str, id: usize) -> Option<Pose> {
+    let filepath = format!("{}/{}.json", dir, id);
+    if Path::new(filepath.as_str()).exists() {
+        Some(Pose::from_file(filepath.as_str()))
+    } else {
+        println!("problem {}: {} does not exist", id, filepath);
+        None
+    }
+}
+
+fn fetch(args: &Args, data: &DataDir) -> Result<(), String> {
+    let client = ClientConfig::load(data)?;
+    args.ids
+        .par_iter()
+        .for_each(|&id| match get_problem(&client, id) {
+            Ok(problem) => {
+                create_parent(data.problem(id).as_str()).unwrap();
+                fs::write(data.problem(id), problem).unwrap();
+                println!("problem {}: downloaded", id);
+            }
+            Err(msg) => println!("problem {}: {}", id, msg),
+        });
+    Ok(())
+}
+
+fn solve_problems(args: &Args, data: &DataDir) -> Result<(), String> {
+    let timeout = args.option_or("timeout", 60000u128)?;
+    let seed = args.option_or("seed", default_seed())?;
+    let config = SolverConfig::from_args(args, data)?;
+    let bonus = bonus_from_args(args)?;
+    let collect_config = if args.flag("collect-bonuses") {
+        Some(CollectConfig::from_args(args)?)
+    } else {
+        None
+    };
+    args.ids.par_iter().for_each(|&id| {
+        if let Some(problem) = load_problem(data, id) {
+            // 他の問題で取れる bonus を使って解き直す
+            if let Some(bonus) = bonus {
+                match find_bonus_source(data, id, bonus, MAX_ID) {
+                    Some(source) => {
+                        println!(
+                            "load problem {} with {} from {} (seed = {}):",
+                            id,
+                            bonus.as_str(),
+                            source,
+                            seed
+                        );
+                        let budget = Budget::Time(timeout);
+                        solve_with_bonus(&problem, seed, budget, id, source, &config, bonus);
+                    }
+                    None => println!("problem {}: no problem gives {}", id, bonus.as_str()),
+                }
+                return;
+            }
+            println!("load problem {} (seed = {}):", id, seed);
+            match solve(&problem) {
+                Some(pose) => {
+                    save_if_better(data, &problem, &pose, id, seed, "exhaustive");
+                }
+                None => {
+                    solve2(&problem, seed, Budget::Time(timeout), id, &config);
+                }
+            }
+            if let Some(collect_config) = &collect_config {
+                collect_best(data, &problem, id, collect_config);
+            }
+        }
+    });
+    Ok(())
+}
+
+fn validate_poses(args: &Args, data: &DataDir) -> Result<(), String> {
+    let best_dir = data.best_dir();
+    let dir = args.option("dir").unwrap_or(best_dir.as_str());
+    let mut valid_count = 0;
+    for &id in args.ids.iter() {
+        let (problem, pose) = match (load_problem(data, id), load_pose(dir, id)) {
+            (Some(problem), Some(pose)) => (problem, pose),
+            _ => continue,
+        };
+        let report = validate(&problem, &pose);
+        if report.is_valid() {
+            valid_count += 1;
+            println!("problem {}: valid", id);
+        } else {
+            println!(
+                "problem {}: invalid (stretched edges {:?}, outside edges {:?}, outside vertices {:?}, vertex count matches {}, bonus errors {:?})",
+                id,
+                report.stretched_edges,
+                report.outside_edges,
+                report.outside_vertices,
+                report.vertex_count_matches,
+                report.bonus_errors
+            );
+        }
+    }
+    println!("{} / {} valid", valid_count, args.ids.len());
+    Ok(())
+}
+
+fn score_poses(args: &Args, data: &DataDir) -> Result<(), String> {
+    let best_dir = data.best_dir();
+    let dir = args.option("dir").unwrap_or(best_dir.as_str());
+    let mut total = 0.0;
+    let mut total_potential = 0.0;
+    for &id in args.ids.iter() {
+        let problem = match load_problem(data, id) {
+            Some(problem) => problem,
+            None => continue,
+        };
+        // valid な解がなければ 0 点
+        let current = load_pose(dir, id)
+            .filter(|pose| validate(&problem, pose).is_valid())
+            .map(|pose| dislike(&problem, &pose));
+        let s = current.map_or(0.0, |d| score(&problem, d, 0.0));
+        let potential = score_potential(&problem, current);
+        total += s;
+        total_potential += potential;
+        match current {
+            Some(d) => println!(
+                "problem {}: dislike {}, score {}, remaining {}",
+                id, d, s, potential
+            ),
+            None => println!("problem {}: no valid pose, remaining {}", id, potential),
+        }
+    }
+    println!("total score {}, remaining {}", total, total_potential);
+    Ok(())
+}
+
+fn render(args: &Args, data: &DataDir) -> Result<(), String> {
+    let best_dir = data.best_dir();
+    let dir = args.option("dir").unwrap_or(best_dir.as_str());
+    for &id in args.ids.iter() {
+        let problem = match load_problem(data, id) {
+            Some(problem) => problem,
+            None => continue,
+        };
+        // 解がなければ問題だけ描く
+        let pose_filepath = format!("{}/{}.json", dir, id);
+        let pose = if Path::new(pose_filepath.as_str()).exists() {
+            Some(Pose::from_file(pose_filepath.as_str()))
+        } else {
+            None
+        };
+        let filepath = data.debug(format!("{}.svg", id).as_str());
+        create_parent(filepath.as_str())?;
+        fs::write(filepath.as_str(), render_svg(&problem, pose.as_ref()))
+            .map_err(|e| e.to_string())?;
+        println!("problem {}: write {}", id, filepath);
+    }
+    Ok(())
+}
+
+fn replay(args: &Args, data: &DataDir) -> Result<(), String> {
+    let frames = args.option_or("frames", 200usize)?;
+    let format = args.option("format").unwrap_or("html");
+    if format != "html" && format != "svg" {
+        return Err(format!("unknown format {}", format));
+    }
+    // --file があればそのファイルだけ、なければ ids の記録のうちあるもの
+    let filepaths = match args.option("file") {
+        Some(filepath) => vec![filepath.to_string()],
+        None => args
+            .ids
+            .iter()
+            .map(|&id| data.debug(format!("trajectory_{}.txt", id).as_str()))
+            .filter(|filepath| Path::new(filepath.as_str()).exists())
+            .collect(),
+    };
+    for filepath in filepaths.iter() {
+        let trajectory = Trajectory::from_file(filepath.as_str())?;
+        let id = trajectory.problem_id;
+        let problem = match load_problem(data, id) {
+            Some(problem) => problem,
+            None => continue,
+        };
+        if format == "html" {
+            let output = data.debug(format!("trajectory_{}.html", id).as_str());
+            create_parent(output.as_str())?;
+            fs::write(
+                output.as_str(),
+                render_animation(&problem, &trajectory, frames),
+            )
+            .map_err(|e| e.to_string())?;
+            println!("problem {}: write {}", id, output);
+        } else {
+            let dir = data.debug(format!("trajectory_{}", id).as_str());
+            fs::create_dir_all(dir.as_str()).map_err(|e| e.to_string())?;
+            let picked = trajectory.pick(frames);
+            for (i, &index) in picked.iter().enumerate() {
+                let svg = render_svg(&problem, Some(&trajectory.pose(index)));
+                fs::write(format!("{}/{:05}.svg", dir, i), svg).map_err(|e| e.to_string())?;
+            }
+            println!("problem {}: write {} frames to {}", id, picked.len(), dir);
+        }
+    }
+    Ok(())
+}
+
+fn compare(args: &Args, data: &DataDir) -> Result<(), String> {
+    let id = match args.ids.as_slice() {
+        [id] => *id,
+        _ => return Err("compare takes exactly one problem id".to_string()),
+    };
+    let problem = load_problem(data, id).ok_or("no problem")?;
+    let a_filepath = match args.option("a") {
+        Some(filepath) => filepath.to_string(),
+        None => data.best(id),
+    };
+    let b_filepath = args.option("b").ok_or("--b <file> is required")?;
+    for filepath in [a_filepath.as_str(), b_filepath] {
+        if !Path::new(filepath).exists() {
+            return Err(format!("{} does not exist", filepath));
+        }
+    }
+    let a = Pose::from_file(a_filepath.as_str());
+    let b = Pose::from_file(b_filepath);
+    let comparison = compare_poses(&problem, &a, &b);
+
+    println!("a: {}", a_filepath);
+    println!("b: {}", b_filepath);
+    println!(
+        "dislike: a {}, b {}, b - a {}",
+        comparison.dislikes.0,
+        comparison.dislikes.1,
+        comparison.dislike_difference()
+    );
+    if comparison.vertex_counts.0 != comparison.vertex_counts.1 {
+        println!(
+            "vertex count: a {}, b {}",
+            comparison.vertex_counts.0, comparison.vertex_counts.1
+        );
+    }
+    println!("moved vertices: {}", comparison.vertices.len());
+    for v in comparison.vertices.iter() {
+        println!(
+            "  vertex {}: ({}, {}) -> ({}, {}), distance {:.3}",
+            v.index,
+            v.a.x,
+            v.a.y,
+            v.b.x,
+            v.b.y,
+            v.distance()
+        );
+    }
+    let status = |acceptable: bool| if acceptable { "ok" } else { "NG" };
+    println!(
+        "edges with changed length: {} (epsilon {})",
+        comparison.edges.len(),
+        problem.epsilon
+    );
+    for e in comparison.edges.iter() {
+        println!(
+            "  edge ({}, {}): original {}, a {} ({:+.6} {}), b {} ({:+.6} {})",
+            e.edge.0,
+            e.edge.1,
+            e.original,
+            e.a,
+            e.a_stretch(),
+            status(e.a_acceptable),
+            e.b,
+            e.b_stretch(),
+            status(e.b_acceptable)
+        );
+    }
+    for (name, report) in [("a", &comparison.reports.0), ("b", &comparison.reports.1)] {
+        let violations = describe_violations(report);
+        if violations.is_empty() {
+            println!("{}: valid", name);
+        } else {
+            println!("{}: invalid", name);
+            for violation in violations.iter() {
+                println!("  {}", violation);
+            }
+        }
+    }
+
+    if let Some(filepath) = args.option("svg") {
+        fs::write(filepath, render_comparison(&problem, &a, &b)).map_err(|e| e.to_string())?;
+        println!("write {}", filepath);
+    }
+    Ok(())
+}
+
+// dir の valid な解のうち、まだ store にないものを加える
+fn import_solutions(args: &Args, data: &DataDir, dir: &str) -> Result<(), String> {
+    let store = SolutionStore::new(data);
+    for &id in args.ids.iter() {
+        let filepath = format!("{}/{}.json", dir, id);
+        if !Path::new(filepath.as_str()).exists() || !data.has_problem(id) {
+            continue;
+        }
+        let problem = Problem::from_file(data.problem(id).as_str());
+        let pose = Pose::from_file(filepath.as_str());
+        if !validate(&problem, &pose).is_valid() || store.find(id, &pose).is_some() {
+            continue;
+        }
+        let meta = SolutionMeta::new("import", read_seed(filepath.as_str()), filepath.as_str());
+        store.add(id, &pose, dislike(&problem, &pose), &meta)?;
+        println!("problem {}: import {}", id, filepath);
+    }
+    Ok(())
+}
+
+fn solutions(args: &Args, data: &DataDir) -> Result<(), String> {
+    if let Some(dir) = args.option("import") {
+        return import_solutions(args, data, dir);
+    }
+    let store = SolutionStore::new(data);
+    let records = match args.option("query").unwrap_or("best") {
+        "best" => store.best_per_problem(),
+        "unsubmitted" => store.unsubmitted_improvements(),
+        "all" => store.records(),
+        query => return Err(format!("unknown query {}", query)),
+    };
+    let describe = |r: &SolutionRecord| {
+        format!(
+            "problem {}: dislike {}, {} by {} (seed {}, {}){} {}",
+            r.problem_id,
+            r.dislike,
+            r.meta.solver,
+            r.meta.author,
+            r.meta.seed.map_or("-".to_string(), |seed| seed.to_string()),
+            r.created,
+            if r.submitted { ", submitted" } else { "" },
+            store.filepath(r)
+        )
+    };
+    for record in records.iter().filter(|r| args.ids.contains(&r.problem_id)) {
+        println!("{}", describe(record));
+    }
+    Ok(())
+}
+
+fn submit(args: &Args, data: &DataDir) -> Result<(), String> {
+    let client = ClientConfig::load(data)?;
+    args.ids.par_iter().for_each(|&id| {
+        let (problem, pose) = match (
+            load_problem(data, id),
+            load_pose(data.best_dir().as_str(), id),
+        ) {
+            (Some(problem), Some(pose)) => (problem, pose),
+            _ => return,
+        };
+        // invalid な解を送っても 0 点なので送らない
+        if !validate(&problem, &pose).is_valid() {
+            println!("problem {}: pose is invalid, skip", id);
+            return;
+        }
+        // 使う bonus を取れていなければ、提出しても弾かれる
+        if let Some(used) = pose
+            .bonuses
+            .iter()
+            .find(|used| !is_unlocked(data, id, used))
+        {
+            println!(
+                "problem {}: {} from {} is not collected yet, skip",
+                id,
+                used.bonus.as_str(),
+                used.problem
+            );
+            return;
+        }
+        match submit_problem(&client, id, &pose) {
+            Ok(_) => {
+                println!("problem {}: submitted", id);
+                let store = SolutionStore::new(data);
+                if let Err(msg) = store.mark_pose_submitted(id, &pose, dislike(&problem, &pose)) {
+                    println!("problem {}: fail to mark submitted: {}", id, msg);
+                }
+            }
+            Err(msg) => println!("fail to submit problem {}: {}", id, msg),
+        }
+    });
+    Ok(())
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    if args.len() < 2 {
+        println!("{}", USAGE);
+        return;
+    }
+    let data = DataDir::from_args(&args);
+    let result = Args::parse_with_flags(&args[2..], MAX_ID, FLAGS).and_then(|parsed| {
+        match args[1].as_str() {
+            "fetch" => fetch(&parsed, &data),
+            "solve" => solve_problems(&parsed, &data),
+            "validate" => validate_poses(&parsed, &data),
+            "score" => score_poses(&parsed, &data),
+            "render" => render(&parsed, &data),
+            "replay" => replay(&parsed, &data),
+            "compare" => compare(&parsed, &data),
+            "solutions" => solutions(&parsed, &data),
+            "submit" => submit(&parsed, &data),
+            command => Err(format!("unknown command {}\n{}", command, USAGE)),
+        }
+    });
+    if let Err(msg) = result {
+        println!("{}", msg);
+        std::process::exit(1);
+    }
+}
@@ -4,6 +4,8 @@ const EPS: f64 = 1e-8;
 
 use lib::algorithm::HoleDistanceCalculator;
 use lib::data::{Point, Pose, Problem};
+use lib::paths::{create_parent, DataDir};
+use std::env;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 
@@ -33,8 +35,9 @@ fn penalty(hdc: &HoleDistanceCalculator, problem: &Problem, pose: &Pose, index:
 }
 
 fn main() {
+    let data = DataDir::from_args(&env::args().collect::<Vec<String>>());
     for id in 1..79 {
-        let problem = Problem::from_file(format!("data/in/{}.json", id).as_str());
+        let problem = Problem::from_file(data.problem(id).as_str());
 
         let hdc = HoleDistanceCalculator::new(&problem.hole);
         let mut max_x = std::f64::MIN;
@@ -57,11 +60,12 @@ fn main() {
         let target_id = 1;
         let mut pose = Pose {
             vertices: problem.figure.vertices.clone(),
+            bonuses: vec![],
         };
 
-        let mut writer = BufWriter::new(
-            File::create(format!("data/debug/penalty_neighbor_{}.txt", id)).unwrap(),
-        );
+        let filepath = data.debug(format!("penalty_neighbor_{}.txt", id).as_str());
+        create_parent(filepath.as_str()).unwrap();
+        let mut writer = BufWriter::new(File::create(filepath).unwrap());
         let mut buffer = String::new();
         for iy in 0..=grid_size {
             let y = (max_y * iy as f64 + min_y * (grid_size - iy) as f64) / grid_size as f64;
@@ -4,16 +4,19 @@ const EPS: f64 = 1e-8;
 
 use lib::algorithm::HoleDistanceCalculator;
 use lib::data::Problem;
+use lib::paths::{create_parent, DataDir};
+use std::env;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 
 fn main() {
+    let data = DataDir::from_args(&env::args().collect::<Vec<String>>());
     for id in 1..79 {
-        let problem = Problem::from_file(format!("data/in/{}.json", id).as_str());
+        let problem = Problem::from_file(data.problem(id).as_str());
         let hdc = HoleDistanceCalculator::new(&problem.hole);
 
-        let mut writer =
-            BufWriter::new(File::create(format!("data/debug/hole_{}.txt", id)).unwrap());
+        create_parent(data.debug_hole(id).as_str()).unwrap();
+        let mut writer = BufWriter::new(File::create(data.debug_hole(id)).unwrap());
         let mut buffer = String::new();
         for tri in hdc.decomposed_triangles.iter() {
             buffer += format!(
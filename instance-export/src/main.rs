@@ -0,0 +1,88 @@
+extern crate lib;
+
+use lib::data::Problem;
+use lib::export::Encoding;
+use lib::paths::DataDir;
+use lib::validation::{dislike, validate};
+use std::env;
+use std::fs;
+
+// instance-export export <id> [wcnf|lp]
+//   in/{id}.json を instances/{id}.wcnf (または .lp) に書き出す
+// instance-export import <id> <wcnf|lp> <solution file>
+//   外部のソルバーの出力を out/{id}.json の Pose に戻す
+// data ディレクトリは環境変数 ICFPC_DATA_DIR か、最後に付けた --data <dir> で変えられる
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    let data = DataDir::from_args(&args);
+    let args = match args.iter().position(|a| a == "--data") {
+        Some(i) => args[..i].to_vec(),
+        None => args,
+    };
+    if args.len() < 3 {
+        println!("usage: instance-export export <id> [wcnf|lp]");
+        println!("       instance-export import <id> <wcnf|lp> <solution file>");
+        return;
+    }
+    let id = args[2].parse::<usize>().unwrap();
+    let problem = Problem::from_file(data.problem(id).as_str());
+    // 大きすぎる問題は外部のソルバーでは解けないので、辺の候補を作る前に止める
+    let encoding = match Encoding::new(&problem) {
+        Ok(encoding) => encoding,
+        Err(msg) => {
+            println!("problem {} is too large: {}", id, msg);
+            return;
+        }
+    };
+    println!(
+        "problem {}: {} vertices, {} points, {} variables",
+        id,
+        encoding.vertex_count,
+        encoding.points.len(),
+        encoding.vertex_count * encoding.points.len()
+    );
+
+    match args[1].as_str() {
+        "export" => {
+            let format = args.get(3).map(|s| s.as_str()).unwrap_or("wcnf");
+            let instance = match format {
+                "wcnf" => encoding.to_wcnf(),
+                "lp" => encoding.to_lp(),
+                _ => panic!("unknown format {}", format),
+            };
+            let dir = data.file("instances");
+            fs::create_dir_all(dir.as_str()).unwrap();
+            let filepath = format!("{}/{}.{}", dir, id, format);
+            fs::write(filepath.as_str(), instance).unwrap();
+            println!("write {}", filepath);
+        }
+        "import" => {
+            if args.len() < 5 {
+                println!("usage: instance-export import <id> <wcnf|lp> <solution file>");
+                return;
+            }
+            let output = fs::read_to_string(args[4].as_str()).unwrap();
+            let pose = match args[3].as_str() {
+                "wcnf" => encoding.pose_from_wcnf_model(output.as_str()),
+                "lp" => encoding.pose_from_lp_solution(output.as_str()),
+                format => panic!("unknown format {}", format),
+            };
+            let pose = match pose {
+                Ok(pose) => pose,
+                Err(msg) => {
+                    println!("fail to import: {}", msg);
+                    return;
+                }
+            };
+            println!(
+                "dislike: {}, valid: {}",
+                dislike(&problem, &pose),
+                validate(&problem, &pose).is_valid()
+            );
+            let filepath = data.out(id);
+            pose.save_file(filepath.clone());
+            println!("write {}", filepath);
+        }
+        command => println!("unknown command {}", command),
+    }
+}
@@ -0,0 +1,109 @@
+use crate::ids::parse_ids;
+
+// コマンドライン引数を、問題番号と --key value の組に分ける
+// 問題番号の指定がなければ 1..=max_id
+pub struct Args {
+    pub ids: Vec<usize>,
+    pub options: Vec<(String, String)>,
+    // 値を取らない --key
+    pub flags: Vec<String>,
+}
+
+impl Args {
+    pub fn parse(args: &[String], max_id: usize) -> Result<Args, String> {
+        Args::parse_with_flags(args, max_id, &[])
+    }
+
+    // flags に含まれる --key は値を取らない
+    pub fn parse_with_flags(
+        args: &[String],
+        max_id: usize,
+        flags: &[&str],
+    ) -> Result<Args, String> {
+        let mut positional = vec![];
+        let mut options = vec![];
+        let mut found_flags = vec![];
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(key) = arg.strip_prefix("--") {
+                if flags.contains(&key) {
+                    found_flags.push(key.to_string());
+                    continue;
+                }
+                match iter.next() {
+                    Some(value) => options.push((key.to_string(), value.clone())),
+                    None => return Err(format!("missing value for --{}", key)),
+                }
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        let mut ids = parse_ids(&positional)?;
+        if ids.is_empty() {
+            ids = (1..=max_id).collect();
+        }
+        Ok(Args {
+            ids,
+            options,
+            flags: found_flags,
+        })
+    }
+
+    pub fn flag(&self, key: &str) -> bool {
+        self.flags.iter().any(|k| k == key)
+    }
+
+    pub fn option(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    // 指定があれば T として読む
+    pub fn parsed_option<T: std::str::FromStr>(&self, key: &str) -> Result<Option<T>, String> {
+        match self.option(key) {
+            Some(value) => value
+                .parse::<T>()
+                .map(Some)
+                .map_err(|_| format!("invalid value for --{}: {}", key, value)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn option_or<T: std::str::FromStr>(&self, key: &str, default: T) -> Result<T, String> {
+        Ok(self.parsed_option(key)?.unwrap_or(default))
+    }
+}
+
+#[test]
+fn test_args() {
+    let args = ["1-3", "--timeout", "500", "7"]
+        .iter()
+        .map(|a| a.to_string())
+        .collect::<Vec<String>>();
+    let parsed = Args::parse(&args, 132).unwrap();
+    assert_eq!(parsed.ids, vec![1, 2, 3, 7]);
+    assert_eq!(parsed.option("timeout"), Some("500"));
+    assert_eq!(parsed.option_or("timeout", 0u128), Ok(500));
+    assert_eq!(parsed.option_or("seed", 42u64), Ok(42));
+    assert_eq!(parsed.parsed_option::<u64>("seed"), Ok(None));
+    assert!(Args::parse(&["--seed".to_string(), "x".to_string()], 5)
+        .unwrap()
+        .parsed_option::<u64>("seed")
+        .is_err());
+
+    let parsed = Args::parse(&[], 5).unwrap();
+    assert_eq!(parsed.ids, vec![1, 2, 3, 4, 5]);
+    assert!(Args::parse(&["--seed".to_string()], 5).is_err());
+
+    let args = ["--relax", "2", "--seed", "3"]
+        .iter()
+        .map(|a| a.to_string())
+        .collect::<Vec<String>>();
+    let parsed = Args::parse_with_flags(&args, 5, &["relax"]).unwrap();
+    assert!(parsed.flag("relax"));
+    assert!(!parsed.flag("seed"));
+    assert_eq!(parsed.ids, vec![2]);
+    assert_eq!(parsed.option("seed"), Some("3"));
+}
@@ -0,0 +1,253 @@
+use crate::data::{BonusType, Point, Pose, Problem, UsedBonus};
+use crate::paths::DataDir;
+use crate::validation::{is_bonus_collected, validate};
+use serde_json::Value;
+
+// source の問題で position に頂点を乗せると、target の問題で bonus が使えるようになる
+#[derive(Clone, Copy, Debug)]
+pub struct BonusEdge {
+    pub source: usize,
+    pub target: usize,
+    pub bonus: BonusType,
+    pub position: Point,
+}
+
+pub struct UnlockGraph {
+    pub edges: Vec<BonusEdge>,
+}
+
+impl UnlockGraph {
+    // problems は (問題番号, 問題) の組
+    pub fn new(problems: &[(usize, Problem)]) -> UnlockGraph {
+        let mut edges = vec![];
+        for (id, problem) in problems.iter() {
+            for b in problem.bonuses.iter() {
+                edges.push(BonusEdge {
+                    source: *id,
+                    target: b.problem,
+                    bonus: b.bonus,
+                    position: b.position,
+                });
+            }
+        }
+        UnlockGraph { edges }
+    }
+
+    // target で使える bonus
+    pub fn incoming(&self, target: usize) -> Vec<BonusEdge> {
+        self.edges
+            .iter()
+            .filter(|e| e.target == target)
+            .copied()
+            .collect()
+    }
+
+    // source で取れる bonus
+    pub fn outgoing(&self, source: usize) -> Vec<BonusEdge> {
+        self.edges
+            .iter()
+            .filter(|e| e.source == source)
+            .copied()
+            .collect()
+    }
+}
+
+#[test]
+fn test_unlock_graph() {
+    let problems = vec![(
+        1,
+        Problem::from_file(crate::paths::DataDir::workspace().problem(1).as_str()),
+    )];
+    let graph = UnlockGraph::new(&problems);
+    assert_eq!(graph.edges.len(), 3);
+    assert_eq!(graph.outgoing(1).len(), 3);
+    let incoming = graph.incoming(35);
+    assert_eq!(incoming.len(), 1);
+    assert_eq!(incoming[0].source, 1);
+    assert_eq!(incoming[0].bonus, BonusType::Globalist);
+}
+
+// source の問題の pose が valid で、target で使う bonus を取っているか
+pub fn unlocks(source: &Problem, pose: &Pose, bonus: BonusType, target: usize) -> bool {
+    validate(source, pose).is_valid()
+        && source
+            .bonuses
+            .iter()
+            .any(|b| b.bonus == bonus && b.problem == target && is_bonus_collected(pose, b))
+}
+
+// problem_id で使う bonus が、取れる問題の best で取れているか
+pub fn is_unlocked(data: &DataDir, problem_id: usize, used: &UsedBonus) -> bool {
+    if !data.has_problem(used.problem) || !data.has_best(used.problem) {
+        return false;
+    }
+    let source = Problem::from_file(data.problem(used.problem).as_str());
+    let best = Pose::from_file(data.best(used.problem).as_str());
+    unlocks(&source, &best, used.bonus, problem_id)
+}
+
+#[test]
+fn test_unlocks() {
+    use crate::data::Bonus;
+    // 10x10 の正方形の穴に、長さ 4 の辺が 1 本。(5, 1) を取ると問題 2 で GLOBALIST が使える
+    let mut source = Problem::new();
+    for &(x, y) in [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)].iter() {
+        source.hole.push(Point::new(x, y));
+    }
+    source.figure.push(Point::new(1.0, 1.0));
+    source.figure.push(Point::new(5.0, 1.0));
+    source.figure.connect(0, 1);
+    source.bonuses.push(Bonus {
+        bonus: BonusType::Globalist,
+        problem: 2,
+        position: Point::new(5.0, 1.0),
+    });
+    let pose = |vertices: &[(f64, f64)]| {
+        let mut pose = Pose::new();
+        for &(x, y) in vertices.iter() {
+            pose.push(Point::new(x, y));
+        }
+        pose
+    };
+
+    let collected = pose(&[(1.0, 1.0), (5.0, 1.0)]);
+    assert!(unlocks(&source, &collected, BonusType::Globalist, 2));
+    assert!(!unlocks(&source, &collected, BonusType::Wallhack, 2));
+    assert!(!unlocks(&source, &collected, BonusType::Globalist, 3));
+    // 位置に乗っていない
+    let missed = pose(&[(2.0, 1.0), (6.0, 1.0)]);
+    assert!(!unlocks(&source, &missed, BonusType::Globalist, 2));
+    // 位置には乗っているが、穴の外に出ていて valid でない
+    let outside = pose(&[(5.0, 1.0), (5.0, -3.0)]);
+    assert!(!unlocks(&source, &outside, BonusType::Globalist, 2));
+}
+
+// target で bonus を使ったときの得点の増加の見積もり
+#[derive(Clone, Copy, Debug)]
+pub struct GainEstimate {
+    pub target: usize,
+    pub bonus: BonusType,
+    pub gain: f64,
+}
+
+// [{"problem": 35, "bonus": "GLOBALIST", "gain": 800}, ...]
+pub fn gain_estimates_from_json(json: &str) -> Vec<GainEstimate> {
+    let v = serde_json::from_str::<Value>(json).unwrap();
+    v.as_array()
+        .unwrap()
+        .iter()
+        .map(|e| GainEstimate {
+            target: e["problem"].as_u64().unwrap() as usize,
+            bonus: BonusType::from_name(e["bonus"].as_str().unwrap()).unwrap(),
+            gain: e["gain"].as_f64().unwrap(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_gain_estimates_from_json() {
+    let estimates =
+        gain_estimates_from_json("[{\"problem\": 35, \"bonus\": \"GLOBALIST\", \"gain\": 800}]");
+    assert_eq!(estimates.len(), 1);
+    assert_eq!(estimates[0].target, 35);
+    assert_eq!(estimates[0].bonus, BonusType::Globalist);
+    assert_eq!(estimates[0].gain, 800.0);
+}
+
+// bonus を 1 つ取って使う計画
+#[derive(Clone, Copy, Debug)]
+pub struct BonusAction {
+    pub edge: BonusEdge,
+    // target でこの bonus を使ったときに増える得点の見積もり
+    pub gain: f64,
+    // source でこの bonus を取るために失う得点の見積もり。既に取れていれば 0
+    pub cost: f64,
+    pub collected: bool,
+}
+
+impl BonusAction {
+    pub fn net_gain(&self) -> f64 {
+        self.gain - self.cost
+    }
+}
+
+// 1 つの問題では bonus を 1 つしか使えないので、target ごとに最も得をするものを選ぶ
+// gain は bonus を使ったときの得点の増加、cost は取るための得点の減少 (取れないなら None)
+pub fn plan<G, C>(graph: &UnlockGraph, gain: G, cost: C) -> Vec<BonusAction>
+where
+    G: Fn(&BonusEdge) -> f64,
+    C: Fn(&BonusEdge) -> Option<f64>,
+{
+    let mut targets = graph.edges.iter().map(|e| e.target).collect::<Vec<_>>();
+    targets.sort_unstable();
+    targets.dedup();
+
+    let mut actions = vec![];
+    for target in targets {
+        let mut best: Option<BonusAction> = None;
+        for edge in graph.incoming(target) {
+            let cost = match cost(&edge) {
+                Some(cost) => cost,
+                None => continue,
+            };
+            let action = BonusAction {
+                edge,
+                gain: gain(&edge),
+                cost,
+                collected: cost == 0.0,
+            };
+            if action.net_gain() <= 0.0 {
+                continue;
+            }
+            let improved = match &best {
+                Some(b) => action.net_gain() > b.net_gain(),
+                None => true,
+            };
+            if improved {
+                best = Some(action);
+            }
+        }
+        if let Some(action) = best {
+            actions.push(action);
+        }
+    }
+    actions
+}
+
+#[test]
+fn test_plan() {
+    let edge = |source, target, bonus| BonusEdge {
+        source,
+        target,
+        bonus,
+        position: Point::new(0.0, 0.0),
+    };
+    let graph = UnlockGraph {
+        edges: vec![
+            edge(1, 3, BonusType::Globalist),
+            edge(2, 3, BonusType::Wallhack),
+            edge(3, 1, BonusType::Superflex),
+            edge(4, 2, BonusType::BreakALeg),
+        ],
+    };
+    let gain = |e: &BonusEdge| match e.bonus {
+        BonusType::Globalist => 100.0,
+        BonusType::Wallhack => 80.0,
+        BonusType::Superflex => 10.0,
+        BonusType::BreakALeg => 50.0,
+    };
+    // 問題 2 は既に取れている。問題 4 の bonus は取れない
+    let cost = |e: &BonusEdge| match e.source {
+        1 => Some(30.0),
+        2 => Some(0.0),
+        3 => Some(20.0),
+        _ => None,
+    };
+    let actions = plan(&graph, gain, cost);
+    // 問題 3 では 100 - 30 より 80 - 0 の方が得。問題 1 では損なので使わない
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].edge.source, 2);
+    assert_eq!(actions[0].edge.target, 3);
+    assert!(actions[0].collected);
+    assert_eq!(actions[0].net_gain(), 80.0);
+}
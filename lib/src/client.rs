@@ -1,36 +1,151 @@
 use crate::data::Pose;
+use crate::paths::DataDir;
 
 use async_std::task;
-use http_client::http_types::{Method, Request};
+use http_client::http_types::{Method, Request, Response};
 use http_client::HttpClient;
+use serde_json::Value;
+use std::env;
+use std::fs;
 
-const API_TOKEN: &str = "";
-const URL: &str = "https://poses.live";
+// API の URL と token を指定する環境変数
+pub const API_URL_ENV: &str = "ICFPC_API_URL";
+pub const API_TOKEN_ENV: &str = "ICFPC_API_TOKEN";
+// 環境変数がなければ data 直下のこのファイルから読む。git には入れない
+//   {"url": "https://poses.live", "token": "..."}
+pub const CONFIG_FILE: &str = "client.json";
+
+const DEFAULT_URL: &str = "https://poses.live";
 
 use http_client::h1::H1Client as Client;
 
-pub async fn async_hello() -> Result<String, String> {
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    pub url: String,
+    pub token: String,
+}
+
+impl ClientConfig {
+    // 環境変数、なければ data/client.json。url は省略できるが token は必須
+    pub fn load(data: &DataDir) -> Result<ClientConfig, String> {
+        let filepath = data.file(CONFIG_FILE);
+        let file = fs::read_to_string(filepath.as_str()).ok();
+        ClientConfig::from_sources(
+            env::var(API_URL_ENV).ok(),
+            env::var(API_TOKEN_ENV).ok(),
+            file.as_deref(),
+            filepath.as_str(),
+        )
+    }
+
+    fn from_sources(
+        url: Option<String>,
+        token: Option<String>,
+        file: Option<&str>,
+        filepath: &str,
+    ) -> Result<ClientConfig, String> {
+        let v = match file {
+            Some(text) => serde_json::from_str::<Value>(text)
+                .map_err(|e| format!("fail to read {}: {}", filepath, e))?,
+            None => Value::Null,
+        };
+        let from_file = |key: &str| v[key].as_str().map(|s| s.to_string());
+        let url = url
+            .filter(|u| !u.is_empty())
+            .or_else(|| from_file("url"))
+            .unwrap_or_else(|| DEFAULT_URL.to_string());
+        let token = token
+            .filter(|t| !t.is_empty())
+            .or_else(|| from_file("token"))
+            .filter(|t| !t.is_empty())
+            .ok_or(format!(
+                "no API token: set {} or write {{\"token\": \"...\"}} to {}",
+                API_TOKEN_ENV, filepath
+            ))?;
+        Ok(ClientConfig {
+            url: url.trim_end_matches('/').to_string(),
+            token,
+        })
+    }
+
+    fn request(&self, method: Method, path: &str) -> Request {
+        let mut req = Request::new(method, format!("{}{}", self.url, path).as_str());
+        req.insert_header("Authorization", format!("Bearer {}", self.token));
+        req
+    }
+}
+
+#[test]
+fn test_client_config() {
+    let file = "{\"url\": \"http://localhost:8000/\", \"token\": \"abc\"}";
+    let config = ClientConfig::from_sources(None, None, Some(file), "client.json").unwrap();
+    assert_eq!(config.url, "http://localhost:8000");
+    assert_eq!(config.token, "abc");
+
+    // 環境変数が優先
+    let config =
+        ClientConfig::from_sources(None, Some("xyz".to_string()), Some(file), "client.json")
+            .unwrap();
+    assert_eq!(config.token, "xyz");
+
+    let config = ClientConfig::from_sources(None, Some("xyz".to_string()), None, "").unwrap();
+    assert_eq!(config.url, DEFAULT_URL);
+
+    let err = ClientConfig::from_sources(None, Some(String::new()), None, "client.json");
+    assert!(err.unwrap_err().contains(API_TOKEN_ENV));
+    assert!(ClientConfig::from_sources(None, None, Some("{"), "client.json").is_err());
+}
+
+// 通信するテスト用。設定がなければ失敗するので、通信するテストは cargo test -- --ignored で動かす
+#[cfg(test)]
+fn test_config() -> ClientConfig {
+    ClientConfig::load(&DataDir::workspace()).unwrap()
+}
+
+// 成功以外のステータスは、ステータスと本文をエラーにする
+async fn response_body(mut res: Response) -> Result<String, String> {
+    let body = res.body_string().await.map_err(|e| e.to_string())?;
+    if res.status().is_success() {
+        Ok(body)
+    } else {
+        Err(format!("{}: {}", res.status(), body))
+    }
+}
+
+#[test]
+fn test_response_body() {
+    use http_client::http_types::StatusCode;
+
+    let mut res = Response::new(StatusCode::Ok);
+    res.set_body("{}");
+    assert_eq!(task::block_on(response_body(res)), Ok("{}".to_string()));
+
+    let mut res = Response::new(StatusCode::Unauthorized);
+    res.set_body("invalid token");
+    let err = task::block_on(response_body(res)).unwrap_err();
+    assert!(err.starts_with("401") && err.contains("invalid token"));
+}
+
+pub async fn async_hello(config: &ClientConfig) -> Result<String, String> {
     let client = Client::new();
 
-    let mut req = Request::new(Method::Get, format!("{}/api/hello", URL).as_str());
-    req.insert_header("Authorization", format!("Bearer {}", API_TOKEN));
+    let req = config.request(Method::Get, "/api/hello");
     let maybe_res = client.send(req).await;
     if let Ok(res) = maybe_res {
-        let mut res = res;
-        let ret = res.body_string().await.unwrap();
-        Ok(ret)
+        response_body(res).await
     } else {
         Err("fail to say hello".to_string())
     }
 }
 
-pub fn hello() -> Result<String, String> {
-    task::block_on(async_hello())
+pub fn hello(config: &ClientConfig) -> Result<String, String> {
+    task::block_on(async_hello(config))
 }
 
 #[test]
+#[ignore = "needs the API token and network"]
 fn test_hello() {
-    let s = hello();
+    let s = hello(&test_config());
     if let Ok(body) = s {
         assert_eq!(body, "{\"hello\":\"xyz600\"}");
     } else {
@@ -38,59 +153,60 @@ fn test_hello() {
     }
 }
 
-pub async fn async_get_problem(id: usize) -> Result<String, String> {
+pub async fn async_get_problem(config: &ClientConfig, id: usize) -> Result<String, String> {
     let client = Client::new();
 
-    let mut req = Request::new(Method::Get, format!("{}/api/problems/{}", URL, id).as_str());
-    req.insert_header("Authorization", format!("Bearer {}", API_TOKEN));
+    let req = config.request(Method::Get, format!("/api/problems/{}", id).as_str());
     let maybe_res = client.send(req).await;
     if let Ok(res) = maybe_res {
-        let mut res = res;
-        let ret = res.body_string().await.unwrap();
-        Ok(ret)
+        // token が違うときなどのエラーの本文を、問題として保存しない
+        response_body(res).await
     } else {
         Err("fail to get problem".to_string())
     }
 }
 
-pub fn get_problem(id: usize) -> Result<String, String> {
-    task::block_on(async_get_problem(id))
+pub fn get_problem(config: &ClientConfig, id: usize) -> Result<String, String> {
+    task::block_on(async_get_problem(config, id))
 }
 
 #[test]
+#[ignore = "needs the API token and network"]
 fn test_get_problem() {
-    let problem = task::block_on(async_get_problem(1));
+    let problem = task::block_on(async_get_problem(&test_config(), 1));
     match problem {
         Err(_msg) => panic!("fail to get problem 1"),
         Ok(_problem_json) => {}
     };
 }
 
-pub async fn async_submit_problem(id: usize, pose: &Pose) -> Result<String, String> {
+pub async fn async_submit_problem(
+    config: &ClientConfig,
+    id: usize,
+    pose: &Pose,
+) -> Result<String, String> {
     let client = Client::new();
 
-    let mut req = Request::new(
+    let mut req = config.request(
         Method::Post,
-        format!("{}/api/problems/{}/solutions", URL, id).as_str(),
+        format!("/api/problems/{}/solutions", id).as_str(),
     );
-    req.insert_header("Authorization", format!("Bearer {}", API_TOKEN));
     req.set_body(pose.to_json());
     println!("{}", pose.to_json());
     let maybe_res = client.send(req).await;
     if let Ok(res) = maybe_res {
-        let mut res = res;
-        let ret = res.body_string().await.unwrap();
-        Ok(ret)
+        response_body(res).await
     } else {
-        Err("fail to get problem".to_string())
+        Err("fail to submit problem".to_string())
     }
 }
 
-pub fn submit_problem(id: usize, pose: &Pose) -> Result<String, String> {
-    task::block_on(async_submit_problem(id, pose))
+pub fn submit_problem(config: &ClientConfig, id: usize, pose: &Pose) -> Result<String, String> {
+    task::block_on(async_submit_problem(config, id, pose))
 }
 
 #[test]
+#[ignore = "needs the API token and network"]
 fn test_submit_problem() {
     use crate::data::Point;
 
@@ -101,7 +217,7 @@ fn test_submit_problem() {
     pose.push(Point::new(0.0, 20.0));
     pose.push(Point::new(20.0, 40.0));
 
-    match submit_problem(ID, &pose) {
+    match submit_problem(&test_config(), ID, &pose) {
         Err(_msg) => panic!("failed to send msg"),
         Ok(body) => println!("{}", body),
     }
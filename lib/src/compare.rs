@@ -0,0 +1,181 @@
+use crate::data::{Point, Pose, Problem};
+use crate::validation::{
+    dislike, is_acceptable_length, posed_figure, validate, ValidationReport, Violation,
+};
+
+// 同じ問題の 2 つの pose (a と b) を比べる
+
+// 頂点の移動
+pub struct VertexDiff {
+    pub index: usize,
+    pub a: Point,
+    pub b: Point,
+}
+
+impl VertexDiff {
+    pub fn distance(&self) -> f64 {
+        self.a.distance(&self.b)
+    }
+}
+
+// 辺の長さ (2 乗) の変化
+pub struct EdgeDiff {
+    pub edge: (usize, usize),
+    pub original: f64,
+    pub a: f64,
+    pub b: f64,
+    // それぞれ長さが epsilon の範囲に収まっているか
+    pub a_acceptable: bool,
+    pub b_acceptable: bool,
+}
+
+impl EdgeDiff {
+    // 元の長さからの変化の割合 (公式の判定と同じく 2 乗の比)
+    pub fn a_stretch(&self) -> f64 {
+        self.a / self.original - 1.0
+    }
+
+    pub fn b_stretch(&self) -> f64 {
+        self.b / self.original - 1.0
+    }
+}
+
+pub struct PoseComparison {
+    // 動いた頂点だけ。頂点の数が違えば共通の番号の分だけ
+    pub vertices: Vec<VertexDiff>,
+    pub vertex_counts: (usize, usize),
+    // 長さが変わった辺だけ。BREAK_A_LEG で figure が違えば、両方にある同じ長さの辺だけ
+    pub edges: Vec<EdgeDiff>,
+    pub dislikes: (f64, f64),
+    pub reports: (ValidationReport, ValidationReport),
+}
+
+impl PoseComparison {
+    // b の dislike - a の dislike
+    pub fn dislike_difference(&self) -> f64 {
+        self.dislikes.1 - self.dislikes.0
+    }
+}
+
+pub fn compare_poses(problem: &Problem, a: &Pose, b: &Pose) -> PoseComparison {
+    let vertices = a
+        .vertices
+        .iter()
+        .zip(b.vertices.iter())
+        .enumerate()
+        .filter(|(_, (pa, pb))| !pa.eq(pb))
+        .map(|(index, (pa, pb))| VertexDiff {
+            index,
+            a: *pa,
+            b: *pb,
+        })
+        .collect::<Vec<VertexDiff>>();
+
+    let figure_a = posed_figure(problem, a);
+    let figure_b = posed_figure(problem, b);
+    let mut edges = vec![];
+    let counts_match =
+        figure_a.vertices.len() == a.vertices.len() && figure_b.vertices.len() == b.vertices.len();
+    if counts_match {
+        for &(v1, v2) in figure_a.edges.iter() {
+            let original = figure_a.vertices[v1].distance2(&figure_a.vertices[v2]);
+            let in_b = figure_b
+                .edges
+                .iter()
+                .any(|&e| e == (v1, v2) || e == (v2, v1))
+                && figure_b.vertices[v1].distance2(&figure_b.vertices[v2]) == original;
+            if !in_b {
+                continue;
+            }
+            let da = a.vertices[v1].distance2(&a.vertices[v2]);
+            let db = b.vertices[v1].distance2(&b.vertices[v2]);
+            if da == db {
+                continue;
+            }
+            edges.push(EdgeDiff {
+                edge: (v1, v2),
+                original,
+                a: da,
+                b: db,
+                a_acceptable: is_acceptable_length(problem, original, da),
+                b_acceptable: is_acceptable_length(problem, original, db),
+            });
+        }
+    }
+
+    PoseComparison {
+        vertices,
+        vertex_counts: (a.vertices.len(), b.vertices.len()),
+        edges,
+        dislikes: (dislike(problem, a), dislike(problem, b)),
+        reports: (validate(problem, a), validate(problem, b)),
+    }
+}
+
+// 検証結果を、破っている規則ごとの 1 行の説明にする
+pub fn describe_violations(report: &ValidationReport) -> Vec<String> {
+    let mut lines = vec![];
+    if !report.vertex_count_matches {
+        lines.push("vertex count does not match the figure".to_string());
+    }
+    if !report.stretched_edges.is_empty() {
+        lines.push(format!(
+            "edge length out of epsilon: edges {:?}",
+            report.stretched_edges
+        ));
+    }
+    if !report.outside_edges.is_empty() {
+        lines.push(format!(
+            "edge crosses the hole boundary: edges {:?}",
+            report.outside_edges
+        ));
+    }
+    if !report.outside_vertices.is_empty() {
+        lines.push(format!(
+            "vertex outside the hole: vertices {:?}",
+            report.outside_vertices
+        ));
+    }
+    for error in report.bonus_errors.iter() {
+        lines.push(format!("bonus: {}", error));
+    }
+    for (bonus, violation) in report.absorbed.iter() {
+        let what = match violation {
+            Violation::StretchedEdge(e) => format!("stretched edge {}", e),
+            Violation::OutsideEdge(e) => format!("outside edge {}", e),
+            Violation::OutsideVertex(v) => format!("outside vertex {}", v),
+        };
+        lines.push(format!("{} absorbs {}", bonus.as_str(), what));
+    }
+    lines
+}
+
+#[test]
+fn test_compare_poses() {
+    let problem = Problem::from_file(crate::paths::DataDir::workspace().problem(11).as_str());
+    let mut a = Pose::new();
+    a.vertices = problem.figure.vertices.clone();
+    let mut b = a.clone();
+    b.vertices[0] = Point::new(10.0, 10.0);
+    b.vertices[2] = Point::new(0.0, 10.0);
+
+    let comparison = compare_poses(&problem, &a, &b);
+    assert_eq!(
+        comparison
+            .vertices
+            .iter()
+            .map(|v| v.index)
+            .collect::<Vec<usize>>(),
+        vec![0, 2]
+    );
+    assert_eq!(comparison.vertices[0].distance(), 200f64.sqrt());
+    // 辺 0-1 は長さが変わらず、0-2 と 1-2 は変わる
+    assert!(comparison.edges.iter().all(|e| e.edge != (0, 1)));
+    assert!(comparison.edges.iter().any(|e| !e.b_acceptable));
+    assert!(!comparison.reports.0.is_valid());
+    assert!(!describe_violations(&comparison.reports.0).is_empty());
+    assert_eq!(
+        comparison.dislike_difference(),
+        comparison.dislikes.1 - comparison.dislikes.0
+    );
+}
@@ -1,3 +1,4 @@
+use crate::paths::create_parent;
 use serde_json::Value;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Read, Write};
@@ -430,6 +431,7 @@ fn edges_to_json(vertices: &Vec<(usize, usize)>) -> String {
     buffer
 }
 
+#[derive(Clone)]
 pub struct Hole {
     pub vertices: Vec<Point>,
 }
@@ -460,6 +462,7 @@ fn test_hole_to_json() {
     assert_eq!(hole.to_json(), "[[2.5, 3.5], [4.5, 5.5]]")
 }
 
+#[derive(Clone)]
 pub struct Figure {
     pub vertices: Vec<Point>,
     pub edges: Vec<(usize, usize)>,
@@ -497,6 +500,25 @@ impl Figure {
         return self.vertices[index1].distance(&self.vertices[index2]);
     }
 
+    // BREAK_A_LEG: 辺 (v1, v2) を、中点に置いた新しい頂点で 2 本に折る
+    // 中点に置くので、折った辺の元の長さ (の 2 乗) はちょうど元の辺の 1/4 になる
+    // 新しい頂点の番号を返す。辺がなければ何もしない
+    pub fn split_edge(&mut self, v1: usize, v2: usize) -> Option<usize> {
+        let index = self
+            .edges
+            .iter()
+            .position(|&e| e == (v1, v2) || e == (v2, v1))?;
+        let middle = (self.vertices[v1] + self.vertices[v2]) / 2.0;
+        let new_vertex = self.vertices.len();
+        self.vertices.push(middle);
+        self.edges.remove(index);
+        self.neighbors[v1].retain(|&v| v != v2);
+        self.neighbors[v2].retain(|&v| v != v1);
+        self.connect(v1, new_vertex);
+        self.connect(new_vertex, v2);
+        Some(new_vertex)
+    }
+
     pub fn to_json(&self) -> String {
         let mut buffer = String::new();
         buffer += "{";
@@ -521,10 +543,79 @@ fn figure_test_to_json() {
     );
 }
 
+#[test]
+fn test_figure_split_edge() {
+    let mut figure = Figure::new();
+    figure.push(Point::new(0.0, 0.0));
+    figure.push(Point::new(3.0, 1.0));
+    figure.push(Point::new(3.0, 4.0));
+    figure.connect(0, 1);
+    figure.connect(1, 2);
+    assert_eq!(figure.split_edge(1, 0), Some(3));
+    assert_eq!(figure.vertices.len(), 4);
+    assert_eq!(figure.edges, vec![(1, 2), (1, 3), (3, 0)]);
+    assert_eq!(figure.neighbors[0], vec![3]);
+    assert_eq!(figure.neighbors[1], vec![2, 3]);
+    // 10 -> 2.5 + 2.5
+    assert_eq!(figure.vertices[0].distance2(&figure.vertices[3]), 2.5);
+    assert_eq!(figure.vertices[3].distance2(&figure.vertices[1]), 2.5);
+    assert_eq!(figure.split_edge(0, 2), None);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BonusType {
+    Globalist,
+    Superflex,
+    Wallhack,
+    BreakALeg,
+}
+
+impl BonusType {
+    pub fn from_name(s: &str) -> Option<BonusType> {
+        match s {
+            "GLOBALIST" => Some(BonusType::Globalist),
+            "SUPERFLEX" => Some(BonusType::Superflex),
+            "WALLHACK" => Some(BonusType::Wallhack),
+            "BREAK_A_LEG" => Some(BonusType::BreakALeg),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BonusType::Globalist => "GLOBALIST",
+            BonusType::Superflex => "SUPERFLEX",
+            BonusType::Wallhack => "WALLHACK",
+            BonusType::BreakALeg => "BREAK_A_LEG",
+        }
+    }
+}
+
+// 問題に置かれている bonus。position に頂点を置くと、problem でその bonus が使えるようになる
+#[derive(Clone, Copy, Debug)]
+pub struct Bonus {
+    pub bonus: BonusType,
+    pub problem: usize,
+    pub position: Point,
+}
+
+impl Bonus {
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"bonus\": \"{}\", \"problem\": {}, \"position\": {}}}",
+            self.bonus.as_str(),
+            self.problem,
+            self.position.to_json()
+        )
+    }
+}
+
+#[derive(Clone)]
 pub struct Problem {
     pub hole: Hole,
     pub figure: Figure,
     pub epsilon: f64,
+    pub bonuses: Vec<Bonus>,
 }
 
 impl Problem {
@@ -533,6 +624,7 @@ impl Problem {
             hole: Hole::new(),
             figure: Figure::new(),
             epsilon: 0.0,
+            bonuses: vec![],
         }
     }
     pub fn to_json(&self) -> String {
@@ -541,6 +633,14 @@ impl Problem {
         buffer += format!("\"hole\": {}", self.hole.to_json()).as_str();
         buffer += format!(", \"figure\": {}", self.figure.to_json()).as_str();
         buffer += format!(", \"epsilon\": {}", self.epsilon).as_str();
+        if !self.bonuses.is_empty() {
+            let bonuses = self
+                .bonuses
+                .iter()
+                .map(|b| b.to_json())
+                .collect::<Vec<String>>();
+            buffer += format!(", \"bonuses\": [{}]", bonuses.join(", ")).as_str();
+        }
         buffer += "}";
         buffer
     }
@@ -582,6 +682,20 @@ impl Problem {
             problem.figure.vertices.push(Point::new(x, y));
         }
         problem.epsilon = v["epsilon"].as_f64().unwrap() / 1e6;
+        // 古い問題ファイルには bonuses がない
+        if let Some(bonuses) = v["bonuses"].as_array() {
+            for bonus in bonuses {
+                let position = bonus["position"].as_array().unwrap();
+                problem.bonuses.push(Bonus {
+                    bonus: BonusType::from_name(bonus["bonus"].as_str().unwrap()).unwrap(),
+                    problem: bonus["problem"].as_u64().unwrap() as usize,
+                    position: Point::new(
+                        position[0].as_f64().unwrap(),
+                        position[1].as_f64().unwrap(),
+                    ),
+                });
+            }
+        }
         problem
     }
 }
@@ -623,11 +737,21 @@ fn test_problem_from_json() {
 
 #[test]
 fn test_problem_from_file() {
-    let problem = Problem::from_file("../data/in/1.json");
+    let problem = Problem::from_file(crate::paths::DataDir::workspace().problem(1).as_str());
     assert_eq!(problem.hole.vertices.len(), 9);
     assert_eq!(problem.hole.vertices[0].x, 45.0);
 }
 
+#[test]
+fn test_problem_bonuses() {
+    let problem = Problem::from_file(crate::paths::DataDir::workspace().problem(1).as_str());
+    assert_eq!(problem.bonuses.len(), 3);
+    assert_eq!(problem.bonuses[0].bonus, BonusType::Globalist);
+    assert_eq!(problem.bonuses[0].problem, 35);
+    assert_eq!(problem.bonuses[0].position.x, 62.0);
+    assert_eq!(problem.bonuses[2].bonus, BonusType::BreakALeg);
+}
+
 #[test]
 fn test_problem_to_json() {
     let mut problem = Problem::new();
@@ -639,13 +763,41 @@ fn test_problem_to_json() {
     assert_eq!("{\"hole\": [[2.5, 3.5], [4.5, 5.5]], \"figure\": {\"edges\": [[0, 1]], \"vertices\": [[2.5, 3.5], [4.5, 5.5]]}, \"epsilon\": 0}", problem.to_json());
 }
 
+// pose で使う bonus。BREAK_A_LEG のときは折る辺も指定する
+#[derive(Clone, Copy, Debug)]
+pub struct UsedBonus {
+    pub bonus: BonusType,
+    pub problem: usize,
+    pub edge: Option<(usize, usize)>,
+}
+
+impl UsedBonus {
+    pub fn to_json(&self) -> String {
+        let mut buffer = format!(
+            "{{\"bonus\": \"{}\", \"problem\": {}",
+            self.bonus.as_str(),
+            self.problem
+        );
+        if let Some((v1, v2)) = self.edge {
+            buffer += format!(", \"edge\": [{}, {}]", v1, v2).as_str();
+        }
+        buffer += "}";
+        buffer
+    }
+}
+
+#[derive(Clone)]
 pub struct Pose {
     pub vertices: Vec<Point>,
+    pub bonuses: Vec<UsedBonus>,
 }
 
 impl Pose {
     pub fn new() -> Pose {
-        Pose { vertices: vec![] }
+        Pose {
+            vertices: vec![],
+            bonuses: vec![],
+        }
     }
 
     pub fn push(&mut self, p: Point) {
@@ -661,6 +813,14 @@ impl Pose {
         buffer += "{";
         buffer += "\"vertices\": ";
         buffer += vertices_to_json(&self.vertices).as_str();
+        if !self.bonuses.is_empty() {
+            let bonuses = self
+                .bonuses
+                .iter()
+                .map(|b| b.to_json())
+                .collect::<Vec<String>>();
+            buffer += format!(", \"bonuses\": [{}]", bonuses.join(", ")).as_str();
+        }
         buffer += "}";
         buffer
     }
@@ -684,10 +844,26 @@ impl Pose {
             let y = p[1].as_f64().unwrap();
             pose.vertices.push(Point::new(x, y));
         }
+        if let Some(bonuses) = v["bonuses"].as_array() {
+            for bonus in bonuses {
+                let edge = bonus["edge"].as_array().map(|e| {
+                    (
+                        e[0].as_u64().unwrap() as usize,
+                        e[1].as_u64().unwrap() as usize,
+                    )
+                });
+                pose.bonuses.push(UsedBonus {
+                    bonus: BonusType::from_name(bonus["bonus"].as_str().unwrap()).unwrap(),
+                    problem: bonus["problem"].as_u64().unwrap() as usize,
+                    edge,
+                });
+            }
+        }
         pose
     }
 
     pub fn save_file(&self, filepath: String) {
+        create_parent(filepath.as_str()).unwrap();
         let mut writer = BufWriter::new(File::create(filepath.as_str()).unwrap());
         if let Err(_msg) = writer.write(self.to_json().as_bytes()) {
             panic!("fail to save result");
@@ -703,10 +879,30 @@ fn test_pose_to_json() {
     assert_eq!(pose.to_json(), "{\"vertices\": [[2.5, 3.5], [4.5, 5.5]]}");
 }
 
+#[test]
+fn test_pose_bonuses_json() {
+    let mut pose = Pose::new();
+    pose.push(Point::new(1.0, 2.0));
+    pose.bonuses.push(UsedBonus {
+        bonus: BonusType::BreakALeg,
+        problem: 38,
+        edge: Some((2, 5)),
+    });
+    let json = pose.to_json();
+    assert_eq!(
+        json,
+        "{\"vertices\": [[1, 2]], \"bonuses\": [{\"bonus\": \"BREAK_A_LEG\", \"problem\": 38, \"edge\": [2, 5]}]}"
+    );
+    let pose = Pose::from_json(json.as_str());
+    assert_eq!(pose.bonuses.len(), 1);
+    assert_eq!(pose.bonuses[0].bonus, BonusType::BreakALeg);
+    assert_eq!(pose.bonuses[0].edge, Some((2, 5)));
+}
+
 #[test]
 fn test_pose_from_file() {
-    let filepath = "../data/best/11.json";
-    let pose = Pose::from_file(filepath);
+    let filepath = crate::paths::DataDir::workspace().best(11);
+    let pose = Pose::from_file(filepath.as_str());
 
     assert_eq!(pose.vertices.len(), 3);
 }
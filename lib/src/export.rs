@@ -0,0 +1,436 @@
+use crate::data::{Point, Pose, Problem};
+use crate::validation::{is_acceptable_length, is_inside_hole, is_segment_inside_hole};
+
+// 変数の数 (頂点数 x 格子点数) の上限
+pub const MAX_VARIABLES: usize = 20000;
+// 辺の候補を作るときに調べる格子点の組 (格子点数の 2 乗) の上限。超えたら候補を作らない
+pub const MAX_PAIRS: usize = 4_000_000;
+
+// 問題を MaxSAT (WCNF) や MILP (LP) の問題として書き出し、外部のソルバーの解を Pose に戻す
+//
+// 変数
+//   x(v, p): figure の頂点 v を hole の内部の格子点 p に置く
+//   o(p): 格子点 p にどれかの頂点が置かれている
+// 制約
+//   各頂点はちょうど 1 つの格子点に置く
+//   辺 (u, v) について、u を p に置くなら、v は p から辺の長さが epsilon に収まり、
+//   線分が hole の内部にある格子点のどれかに置く
+// 目的関数
+//   dislike。hole の頂点 h から格子点までの距離 (2 乗) を近い順に L_0 < L_1 < ... とすると、
+//   最も近い頂点までの距離は L_0 + sum_i (L_i - L_{i-1}) * [距離 L_{i-1} 以内に頂点がない]
+//   WCNF では c(h, i): 距離 L_i 以内に頂点がある、を L_i の順に鎖にしてつなぐ
+//   LP では h を置かれている格子点のどれかに割り当て、その距離を足す
+pub struct Encoding {
+    // hole の内部 (境界を含む) の格子点
+    pub points: Vec<Point>,
+    pub vertex_count: usize,
+    edges: Vec<(usize, usize)>,
+    // compatible[e][p]: 辺 e の一端を p に置いたとき、もう一端を置ける格子点
+    compatible: Vec<Vec<Vec<usize>>>,
+    // hole の頂点ごとの、距離 (2 乗) の段階と、ちょうどその距離にある格子点
+    levels: Vec<Vec<(i64, Vec<usize>)>>,
+}
+
+// 制約 (hard clause) と目的関数 (soft clause, 重みと clause の組)
+struct Clauses {
+    hard: Vec<Vec<i64>>,
+    soft: Vec<(i64, Vec<i64>)>,
+}
+
+impl Encoding {
+    pub fn new(problem: &Problem) -> Result<Encoding, String> {
+        let hole = &problem.hole;
+        let min_x = hole.vertices.iter().map(|p| p.x as i64).min().unwrap();
+        let max_x = hole.vertices.iter().map(|p| p.x as i64).max().unwrap();
+        let min_y = hole.vertices.iter().map(|p| p.y as i64).min().unwrap();
+        let max_y = hole.vertices.iter().map(|p| p.y as i64).max().unwrap();
+        let mut points = vec![];
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let p = Point::new(x as f64, y as f64);
+                if is_inside_hole(hole, &p) {
+                    points.push(p);
+                }
+            }
+        }
+        let variables = problem.figure.vertices.len() * points.len();
+        if variables > MAX_VARIABLES {
+            return Err(format!(
+                "{} vertices on {} points make {} variables, more than {}",
+                problem.figure.vertices.len(),
+                points.len(),
+                variables,
+                MAX_VARIABLES
+            ));
+        }
+        let pairs = points.len() * points.len();
+        if pairs > MAX_PAIRS {
+            return Err(format!(
+                "{} points make {} pairs for the edge candidates, more than {}",
+                points.len(),
+                pairs,
+                MAX_PAIRS
+            ));
+        }
+
+        // 同じ長さの辺は同じ候補になる
+        let mut cache: Vec<(f64, Vec<Vec<usize>>)> = vec![];
+        let mut compatible = vec![];
+        for &(v1, v2) in problem.figure.edges.iter() {
+            let orig_dist = problem.figure.vertices[v1].distance2(&problem.figure.vertices[v2]);
+            if let Some((_, c)) = cache.iter().find(|(d, _)| *d == orig_dist) {
+                compatible.push(c.clone());
+                continue;
+            }
+            let c = points
+                .iter()
+                .map(|p| {
+                    (0..points.len())
+                        .filter(|&q| {
+                            is_acceptable_length(problem, orig_dist, p.distance2(&points[q]))
+                                && is_segment_inside_hole(hole, p, &points[q])
+                        })
+                        .collect::<Vec<usize>>()
+                })
+                .collect::<Vec<Vec<usize>>>();
+            cache.push((orig_dist, c.clone()));
+            compatible.push(c);
+        }
+
+        let mut levels = vec![];
+        for h in hole.vertices.iter() {
+            let mut order = (0..points.len())
+                .map(|p| (h.distance2(&points[p]) as i64, p))
+                .collect::<Vec<(i64, usize)>>();
+            order.sort_unstable();
+            let mut hole_levels: Vec<(i64, Vec<usize>)> = vec![];
+            for (i, &(d, p)) in order.iter().enumerate() {
+                if i == 0 || order[i - 1].0 != d {
+                    hole_levels.push((d, vec![]));
+                }
+                hole_levels.last_mut().unwrap().1.push(p);
+            }
+            levels.push(hole_levels);
+        }
+
+        Ok(Encoding {
+            points,
+            vertex_count: problem.figure.vertices.len(),
+            edges: problem.figure.edges.clone(),
+            compatible,
+            levels,
+        })
+    }
+
+    // WCNF の変数番号 (1 から)
+    fn x(&self, v: usize, p: usize) -> usize {
+        v * self.points.len() + p + 1
+    }
+
+    fn o(&self, p: usize) -> usize {
+        self.vertex_count * self.points.len() + p + 1
+    }
+
+    fn clauses(&self) -> Clauses {
+        let n = self.vertex_count;
+        let m = self.points.len();
+        let mut hard = vec![];
+        let mut next_var = (n * m + m + 1) as i64;
+
+        for v in 0..n {
+            // 少なくとも 1 つ
+            hard.push((0..m).map(|p| self.x(v, p) as i64).collect());
+            // 高々 1 つ (sequential counter)
+            let s = (0..m)
+                .map(|_| {
+                    next_var += 1;
+                    next_var - 1
+                })
+                .collect::<Vec<i64>>();
+            for p in 0..m {
+                let x = self.x(v, p) as i64;
+                hard.push(vec![-x, s[p]]);
+                if p > 0 {
+                    hard.push(vec![-s[p - 1], s[p]]);
+                    hard.push(vec![-x, -s[p - 1]]);
+                }
+            }
+        }
+
+        for (e, &(v1, v2)) in self.edges.iter().enumerate() {
+            for (a, b) in [(v1, v2), (v2, v1)].iter() {
+                for p in 0..m {
+                    let mut clause = vec![-(self.x(*a, p) as i64)];
+                    for &q in self.compatible[e][p].iter() {
+                        clause.push(self.x(*b, q) as i64);
+                    }
+                    hard.push(clause);
+                }
+            }
+        }
+
+        for p in 0..m {
+            let mut clause = vec![-(self.o(p) as i64)];
+            for v in 0..n {
+                clause.push(self.x(v, p) as i64);
+            }
+            hard.push(clause);
+        }
+
+        let mut soft = vec![];
+        for hole_levels in self.levels.iter() {
+            let mut prev: Option<i64> = None;
+            for (i, (d, points)) in hole_levels.iter().enumerate() {
+                let c = next_var;
+                next_var += 1;
+                // c(h, i) なら c(h, i - 1) か、ちょうど距離 L_i の格子点に頂点がある
+                let mut clause = vec![-c];
+                clause.extend(prev);
+                clause.extend(points.iter().map(|&p| self.o(p) as i64));
+                hard.push(clause);
+                if i > 0 {
+                    soft.push((d - hole_levels[i - 1].0, vec![prev.unwrap()]));
+                }
+                prev = Some(c);
+            }
+        }
+        Clauses { hard, soft }
+    }
+
+    // hard clause の重みは soft clause の重みの合計より大きくする
+    pub fn to_wcnf(&self) -> String {
+        let Clauses { hard, soft } = self.clauses();
+        let var_count = hard
+            .iter()
+            .chain(soft.iter().map(|(_, c)| c))
+            .flat_map(|c| c.iter().map(|l| l.abs()))
+            .max()
+            .unwrap_or(0);
+        let top = soft.iter().map(|(w, _)| w).sum::<i64>() + 1;
+
+        let mut buffer = String::new();
+        buffer += format!("p wcnf {} {} {}\n", var_count, hard.len() + soft.len(), top).as_str();
+        for clause in hard.iter() {
+            buffer += format!("{} {} 0\n", top, join(clause)).as_str();
+        }
+        for (weight, clause) in soft.iter() {
+            buffer += format!("{} {} 0\n", weight, join(clause)).as_str();
+        }
+        buffer
+    }
+
+    // CPLEX LP 形式。w_{h}_{p} は hole の頂点 h を格子点 p に割り当てる
+    pub fn to_lp(&self) -> String {
+        let n = self.vertex_count;
+        let m = self.points.len();
+        let x = |v: usize, p: usize| format!("x_{}_{}", v, p);
+        let o = |p: usize| format!("o_{}", p);
+        let w = |h: usize, p: usize| format!("w_{}_{}", h, p);
+
+        let mut objective = vec![];
+        for (h, hole_levels) in self.levels.iter().enumerate() {
+            for (d, points) in hole_levels.iter() {
+                objective.extend(points.iter().map(|&p| format!("+ {} {}", d, w(h, p))));
+            }
+        }
+        let mut buffer = String::new();
+        buffer += "Minimize\n obj:";
+        buffer += lp_terms(&objective).as_str();
+        buffer += "\nSubject To\n";
+
+        for v in 0..n {
+            let terms = (0..m).map(|p| format!("+ {}", x(v, p))).collect::<Vec<_>>();
+            buffer += format!(" one_{}:{} = 1\n", v, lp_terms(&terms)).as_str();
+        }
+        for (e, &(v1, v2)) in self.edges.iter().enumerate() {
+            for (a, b) in [(v1, v2), (v2, v1)].iter() {
+                for p in 0..m {
+                    let mut terms = vec![format!("+ {}", x(*a, p))];
+                    terms.extend(
+                        self.compatible[e][p]
+                            .iter()
+                            .map(|&q| format!("- {}", x(*b, q))),
+                    );
+                    buffer +=
+                        format!(" edge_{}_{}_{}:{} <= 0\n", e, a, p, lp_terms(&terms)).as_str();
+                }
+            }
+        }
+        for p in 0..m {
+            let mut terms = vec![format!("+ {}", o(p))];
+            terms.extend((0..n).map(|v| format!("- {}", x(v, p))));
+            buffer += format!(" occupied_{}:{} <= 0\n", p, lp_terms(&terms)).as_str();
+        }
+        for h in 0..self.levels.len() {
+            let terms = (0..m).map(|p| format!("+ {}", w(h, p))).collect::<Vec<_>>();
+            buffer += format!(" assign_{}:{} = 1\n", h, lp_terms(&terms)).as_str();
+            for p in 0..m {
+                buffer += format!(" near_{}_{}: {} - {} <= 0\n", h, p, w(h, p), o(p)).as_str();
+            }
+        }
+
+        buffer += "Binary\n";
+        for v in 0..n {
+            for p in 0..m {
+                buffer += format!(" {}\n", x(v, p)).as_str();
+            }
+        }
+        for p in 0..m {
+            buffer += format!(" {}\n", o(p)).as_str();
+        }
+        buffer += "End\n";
+        buffer
+    }
+
+    fn pose_from_positions(&self, positions: &[Option<usize>]) -> Result<Pose, String> {
+        let mut pose = Pose::new();
+        for (v, p) in positions.iter().enumerate() {
+            match p {
+                Some(p) => pose.push(self.points[*p]),
+                None => return Err(format!("vertex {} is not assigned", v)),
+            }
+        }
+        Ok(pose)
+    }
+
+    // MaxSAT ソルバーの出力の "v 1 -2 3 ..." の行 (行頭の v はなくてもよい)
+    pub fn pose_from_wcnf_model(&self, model: &str) -> Result<Pose, String> {
+        let m = self.points.len();
+        let mut positions = vec![None; self.vertex_count];
+        for line in model.lines() {
+            let line = line.trim();
+            let line = match line.strip_prefix('v') {
+                Some(rest) => rest,
+                None if line.starts_with(|c: char| c == '-' || c.is_ascii_digit()) => line,
+                None => continue,
+            };
+            for token in line.split_whitespace() {
+                let literal = token.parse::<i64>().map_err(|e| e.to_string())?;
+                if literal <= 0 || literal as usize > self.vertex_count * m {
+                    continue;
+                }
+                let index = literal as usize - 1;
+                positions[index / m] = Some(index % m);
+            }
+        }
+        self.pose_from_positions(&positions)
+    }
+
+    // MILP ソルバーの解ファイル。"x_{v}_{p}" の変数名の後に値が続く行を読む
+    pub fn pose_from_lp_solution(&self, solution: &str) -> Result<Pose, String> {
+        let mut positions = vec![None; self.vertex_count];
+        for line in solution.lines() {
+            let tokens = line.split_whitespace().collect::<Vec<&str>>();
+            for (i, token) in tokens.iter().enumerate() {
+                let name = match token.strip_prefix("x_") {
+                    Some(name) => name,
+                    None => continue,
+                };
+                let value = tokens
+                    .get(i + 1)
+                    .and_then(|t| t.parse::<f64>().ok())
+                    .unwrap_or(0.0);
+                if value < 0.5 {
+                    continue;
+                }
+                let ids = name.split('_').collect::<Vec<&str>>();
+                if ids.len() != 2 {
+                    return Err(format!("unknown variable {}", token));
+                }
+                let v = ids[0].parse::<usize>().map_err(|e| e.to_string())?;
+                let p = ids[1].parse::<usize>().map_err(|e| e.to_string())?;
+                if v >= self.vertex_count || p >= self.points.len() {
+                    return Err(format!("unknown variable {}", token));
+                }
+                positions[v] = Some(p);
+            }
+        }
+        self.pose_from_positions(&positions)
+    }
+}
+
+fn join(clause: &[i64]) -> String {
+    clause
+        .iter()
+        .map(|l| l.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// LP 形式は 1 行の長さに制限があるので、項を数個ずつ改行する
+fn lp_terms(terms: &[String]) -> String {
+    terms
+        .chunks(8)
+        .map(|chunk| format!(" {}", chunk.join(" ")))
+        .collect::<Vec<String>>()
+        .join("\n  ")
+}
+
+#[cfg(test)]
+fn export_test_problem() -> Problem {
+    // 2x2 の正方形の穴に、長さ 2 の辺が 1 本
+    let mut problem = Problem::new();
+    for &(x, y) in [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)].iter() {
+        problem.hole.push(Point::new(x, y));
+    }
+    problem.figure.push(Point::new(0.0, 0.0));
+    problem.figure.push(Point::new(2.0, 0.0));
+    problem.figure.connect(0, 1);
+    problem
+}
+
+#[test]
+fn test_encoding() {
+    let problem = export_test_problem();
+    let encoding = Encoding::new(&problem).unwrap();
+    assert_eq!(encoding.points.len(), 9);
+    // (0, 0) からは (2, 0) と (0, 2) だけ
+    assert_eq!(encoding.compatible[0][0], vec![2, 6]);
+
+    let wcnf = encoding.to_wcnf();
+    // hole の各頂点から最も遠い格子点までの距離は 8 なので、soft clause の重みの合計は 4 * 8
+    assert!(wcnf.lines().next().unwrap().ends_with(" 33"));
+    let lp = encoding.to_lp();
+    assert!(lp.starts_with("Minimize"));
+    assert!(lp.contains(" one_0: + x_0_0 + x_0_1 "));
+    assert!(lp.ends_with("End\n"));
+
+    // 200x200 の穴は格子点が多すぎる
+    let mut large = export_test_problem();
+    large.hole.clear();
+    for &(x, y) in [(0.0, 0.0), (200.0, 0.0), (200.0, 200.0), (0.0, 200.0)].iter() {
+        large.hole.push(Point::new(x, y));
+    }
+    assert!(Encoding::new(&large).is_err());
+
+    // 60x60 の穴なら変数の数は上限以下だが、格子点の組が多すぎる
+    let mut wide = export_test_problem();
+    wide.hole.clear();
+    for &(x, y) in [(0.0, 0.0), (60.0, 0.0), (60.0, 60.0), (0.0, 60.0)].iter() {
+        wide.hole.push(Point::new(x, y));
+    }
+    assert!(wide.figure.vertices.len() * 61 * 61 <= MAX_VARIABLES);
+    match Encoding::new(&wide) {
+        Ok(_) => panic!("60x60 hole should be too large"),
+        Err(msg) => assert!(msg.contains("pairs")),
+    }
+}
+
+#[test]
+fn test_import() {
+    let problem = export_test_problem();
+    let encoding = Encoding::new(&problem).unwrap();
+
+    // 頂点 0 を (0, 0)、頂点 1 を (2, 0) に置く
+    let model = format!("s OPTIMUM FOUND\nv 1 -2 {} -4\n", 9 + 3);
+    let pose = encoding.pose_from_wcnf_model(model.as_str()).unwrap();
+    assert_eq!(pose.vertices[0].x, 0.0);
+    assert_eq!(pose.vertices[1].x, 2.0);
+
+    let solution = "x_0_8 1\nx_0_0 0\nx_1_6 1\n";
+    let pose = encoding.pose_from_lp_solution(solution).unwrap();
+    assert_eq!((pose.vertices[0].x, pose.vertices[0].y), (2.0, 2.0));
+    assert_eq!((pose.vertices[1].x, pose.vertices[1].y), (0.0, 2.0));
+
+    assert!(encoding.pose_from_lp_solution("x_0_8 1\n").is_err());
+}
@@ -0,0 +1,41 @@
+// 問題番号の指定を読む
+// "3" や "1-10" (両端を含む) を、カンマ区切りや複数の引数で並べられる
+// 例: ["1-3,7", "10"] -> [1, 2, 3, 7, 10]
+pub fn parse_ids(args: &[String]) -> Result<Vec<usize>, String> {
+    let mut ids = vec![];
+    for arg in args.iter() {
+        for item in arg.split(',').filter(|s| !s.is_empty()) {
+            let parse = |s: &str| {
+                s.trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid problem id: {}", item))
+            };
+            match item.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (parse(first)?, parse(last)?);
+                    if first > last {
+                        return Err(format!("invalid problem range: {}", item));
+                    }
+                    ids.extend(first..=last);
+                }
+                None => ids.push(parse(item)?),
+            }
+        }
+    }
+    ids.sort_unstable();
+    ids.dedup();
+    Ok(ids)
+}
+
+#[test]
+fn test_parse_ids() {
+    let args = |s: &[&str]| s.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+    assert_eq!(
+        parse_ids(&args(&["1-3,7", "10"])).unwrap(),
+        vec![1, 2, 3, 7, 10]
+    );
+    assert_eq!(parse_ids(&args(&["5", "4-6"])).unwrap(), vec![4, 5, 6]);
+    assert_eq!(parse_ids(&args(&[])).unwrap(), Vec::<usize>::new());
+    assert!(parse_ids(&args(&["3-1"])).is_err());
+    assert!(parse_ids(&args(&["x"])).is_err());
+}
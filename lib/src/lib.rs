@@ -1,3 +1,14 @@
 pub mod algorithm;
+pub mod args;
+pub mod bonus;
 pub mod client;
+pub mod compare;
 pub mod data;
+pub mod export;
+pub mod ids;
+pub mod paths;
+pub mod render;
+pub mod score;
+pub mod store;
+pub mod trajectory;
+pub mod validation;
@@ -0,0 +1,148 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// data ディレクトリの場所を指定する環境変数
+pub const DATA_DIR_ENV: &str = "ICFPC_DATA_DIR";
+
+// data ディレクトリの中の配置
+//   in/{id}.json       問題
+//   best/{id}.json     問題ごとの最良の解 (と {id}.meta.json)
+//   out/{id}.json      valid にならなかった解など
+//   debug/             デバッグ用の出力
+//   solutions/         見つけた valid な解の全て (store.rs)
+// パスを返すだけでディレクトリは作らない。書くときに create_parent で作る
+#[derive(Clone, Debug)]
+pub struct DataDir {
+    root: String,
+}
+
+impl DataDir {
+    pub fn new(root: &str) -> DataDir {
+        DataDir {
+            root: root.trim_end_matches('/').to_string(),
+        }
+    }
+
+    // 環境変数 ICFPC_DATA_DIR、なければ ./data
+    pub fn from_env() -> DataDir {
+        match env::var(DATA_DIR_ENV) {
+            Ok(root) if !root.is_empty() => DataDir::new(root.as_str()),
+            _ => DataDir::new("data"),
+        }
+    }
+
+    // コマンドライン引数の --data <dir>、なければ環境変数
+    pub fn from_args(args: &[String]) -> DataDir {
+        match args.iter().position(|a| a == "--data") {
+            Some(i) if i + 1 < args.len() => DataDir::new(args[i + 1].as_str()),
+            _ => DataDir::from_env(),
+        }
+    }
+
+    // このリポジトリの data。テストで使う
+    pub fn workspace() -> DataDir {
+        DataDir::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../data"))
+    }
+
+    pub fn root(&self) -> &str {
+        self.root.as_str()
+    }
+
+    fn dir(&self, name: &str) -> String {
+        format!("{}/{}", self.root, name)
+    }
+
+    pub fn in_dir(&self) -> String {
+        self.dir("in")
+    }
+
+    pub fn best_dir(&self) -> String {
+        self.dir("best")
+    }
+
+    pub fn out_dir(&self) -> String {
+        self.dir("out")
+    }
+
+    pub fn debug_dir(&self) -> String {
+        self.dir("debug")
+    }
+
+    pub fn solutions_dir(&self) -> String {
+        self.dir("solutions")
+    }
+
+    pub fn problem(&self, id: usize) -> String {
+        format!("{}/{}.json", self.in_dir(), id)
+    }
+
+    pub fn has_problem(&self, id: usize) -> bool {
+        Path::new(self.problem(id).as_str()).exists()
+    }
+
+    pub fn best(&self, id: usize) -> String {
+        format!("{}/{}.json", self.best_dir(), id)
+    }
+
+    pub fn has_best(&self, id: usize) -> bool {
+        Path::new(self.best(id).as_str()).exists()
+    }
+
+    pub fn out(&self, id: usize) -> String {
+        format!("{}/{}.json", self.out_dir(), id)
+    }
+
+    pub fn debug(&self, name: &str) -> String {
+        format!("{}/{}", self.debug_dir(), name)
+    }
+
+    pub fn debug_hole(&self, id: usize) -> String {
+        self.debug(format!("hole_{}.txt", id).as_str())
+    }
+
+    // data 直下のファイルやディレクトリ (bonus_gains.json など)
+    pub fn file(&self, name: &str) -> String {
+        format!("{}/{}", self.root, name)
+    }
+}
+
+// filepath に書く前に、親のディレクトリがなければ作る
+pub fn create_parent(filepath: &str) -> Result<(), String> {
+    match Path::new(filepath).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Default for DataDir {
+    fn default() -> DataDir {
+        DataDir::from_env()
+    }
+}
+
+#[test]
+fn test_data_dir() {
+    let root = env::temp_dir().join(format!("data_dir_test_{}", std::process::id()));
+    let data = DataDir::new(root.to_str().unwrap());
+    assert_eq!(data.best(3), format!("{}/best/3.json", data.root()));
+    assert!(!root.exists());
+    create_parent(data.best(3).as_str()).unwrap();
+    assert!(root.join("best").is_dir());
+    assert_eq!(
+        data.debug_hole(5),
+        format!("{}/debug/hole_5.txt", data.root())
+    );
+    assert!(!data.has_problem(1));
+    fs::remove_dir_all(root).unwrap();
+
+    let args = ["1-3", "--data", "/tmp/scratch/"]
+        .iter()
+        .map(|a| a.to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(DataDir::from_args(&args).root(), "/tmp/scratch");
+
+    assert!(DataDir::workspace().has_problem(1));
+}
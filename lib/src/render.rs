@@ -0,0 +1,370 @@
+use crate::data::{BonusType, Point, Pose, Problem};
+use crate::trajectory::Trajectory;
+use crate::validation::{dislike, is_bonus_collected, posed_figure, validate, Violation};
+
+// problem と pose を SVG に描く
+//   hole: 灰色で塗る
+//   元の figure: 薄い青の点線
+//   pose: 緑。長さが合わない辺は赤、hole からはみ出す辺は紫、hole の外の頂点は赤い丸
+//         bonus が吸収した違反は同じ色の点線
+//   bonus の位置: 種類ごとの色の丸。pose が取っていれば塗りつぶす
+
+// 出力する画像の長い方の辺の長さ (px)
+const IMAGE_SIZE: f64 = 800.0;
+
+const HOLE_COLOR: &str = "#d3d3d3";
+const FIGURE_COLOR: &str = "#9ecae1";
+const POSE_COLOR: &str = "#2ca02c";
+const STRETCHED_COLOR: &str = "#d62728";
+const OUTSIDE_EDGE_COLOR: &str = "#9467bd";
+const OUTSIDE_VERTEX_COLOR: &str = "#d62728";
+// 2 つの pose を重ねるときの色
+const POSE_A_COLOR: &str = "#1f77b4";
+const POSE_B_COLOR: &str = "#ff7f0e";
+const DISPLACEMENT_COLOR: &str = "#7f7f7f";
+
+fn bonus_color(bonus: BonusType) -> &'static str {
+    match bonus {
+        BonusType::Globalist => "#e6b800",
+        BonusType::Superflex => "#17becf",
+        BonusType::Wallhack => "#ff7f0e",
+        BonusType::BreakALeg => "#1f77b4",
+    }
+}
+
+fn line(p1: &Point, p2: &Point, color: &str, width: f64, dashed: bool) -> String {
+    format!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\"{}/>\n",
+        p1.x,
+        p1.y,
+        p2.x,
+        p2.y,
+        color,
+        width,
+        if dashed {
+            format!(" stroke-dasharray=\"{} {}\"", width * 3.0, width * 2.0)
+        } else {
+            String::new()
+        }
+    )
+}
+
+fn circle(p: &Point, r: f64, color: &str, filled: bool, width: f64, title: &str) -> String {
+    format!(
+        "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" stroke=\"{}\" stroke-width=\"{}\" fill=\"{}\"><title>{}</title></circle>\n",
+        p.x,
+        p.y,
+        r,
+        color,
+        width,
+        if filled { color } else { "none" },
+        title
+    )
+}
+
+// svg の開始から、hole、元の figure、bonus の位置まで
+// 返り値は svg と、線の太さなどの単位と、左上の座標
+// bonus は collector があれば、その pose が取っているものを塗りつぶす
+fn begin_svg(problem: &Problem, poses: &[&Pose], collector: Option<&Pose>) -> (String, f64, Point) {
+    // 描くもの全てが入る範囲
+    let mut points = problem.hole.vertices.clone();
+    points.extend(problem.figure.vertices.iter().cloned());
+    points.extend(problem.bonuses.iter().map(|b| b.position));
+    for pose in poses.iter() {
+        points.extend(pose.vertices.iter().cloned());
+    }
+    let min_x = points.iter().map(|p| p.x).fold(f64::MAX, f64::min);
+    let min_y = points.iter().map(|p| p.y).fold(f64::MAX, f64::min);
+    let max_x = points.iter().map(|p| p.x).fold(f64::MIN, f64::max);
+    let max_y = points.iter().map(|p| p.y).fold(f64::MIN, f64::max);
+    let size = (max_x - min_x).max(max_y - min_y).max(1.0);
+    let margin = size * 0.05;
+    let width = max_x - min_x + margin * 2.0;
+    let height = max_y - min_y + margin * 2.0;
+    // 線の太さなどは座標の単位で指定するので、大きさに合わせる
+    let unit = size / 400.0;
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">\n",
+        (width / size * IMAGE_SIZE).round(),
+        (height / size * IMAGE_SIZE).round(),
+        min_x - margin,
+        min_y - margin,
+        width,
+        height
+    );
+    svg += format!(
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"white\"/>\n",
+        min_x - margin,
+        min_y - margin,
+        width,
+        height
+    )
+    .as_str();
+
+    let hole = problem
+        .hole
+        .vertices
+        .iter()
+        .map(|p| format!("{},{}", p.x, p.y))
+        .collect::<Vec<String>>()
+        .join(" ");
+    svg += format!(
+        "<polygon points=\"{}\" fill=\"{}\" stroke=\"gray\" stroke-width=\"{}\"/>\n",
+        hole,
+        HOLE_COLOR,
+        unit * 2.0
+    )
+    .as_str();
+
+    for &(v1, v2) in problem.figure.edges.iter() {
+        let (p1, p2) = (&problem.figure.vertices[v1], &problem.figure.vertices[v2]);
+        svg += line(p1, p2, FIGURE_COLOR, unit * 2.0, true).as_str();
+    }
+
+    for bonus in problem.bonuses.iter() {
+        let collected = collector.is_some_and(|pose| is_bonus_collected(pose, bonus));
+        let title = format!(
+            "{} for problem {}{}",
+            bonus.bonus.as_str(),
+            bonus.problem,
+            if collected { " (collected)" } else { "" }
+        );
+        svg += circle(
+            &bonus.position,
+            unit * 8.0,
+            bonus_color(bonus.bonus),
+            collected,
+            unit * 2.0,
+            title.as_str(),
+        )
+        .as_str();
+    }
+
+    (svg, unit, Point::new(min_x - margin, min_y - margin))
+}
+
+// 左上に line_no 行目の説明を書く
+fn caption(origin: &Point, unit: f64, line_no: usize, s: &str) -> String {
+    format!(
+        "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"sans-serif\">{}</text>\n",
+        origin.x + unit * 4.0,
+        origin.y + unit * 16.0 * (line_no + 1) as f64,
+        unit * 14.0,
+        s
+    )
+}
+
+pub fn render_svg(problem: &Problem, pose: Option<&Pose>) -> String {
+    let poses = pose.iter().cloned().collect::<Vec<&Pose>>();
+    let (mut svg, unit, origin) = begin_svg(problem, &poses, pose);
+
+    if let Some(pose) = pose {
+        let figure = posed_figure(problem, pose);
+        let report = validate(problem, pose);
+        if report.vertex_count_matches {
+            // 違反ごとの色。bonus が吸収したものは点線
+            let mut edge_colors = vec![(POSE_COLOR, false); figure.edges.len()];
+            let mut vertex_colors = vec![None; pose.vertices.len()];
+            for &e in report.outside_edges.iter() {
+                edge_colors[e] = (OUTSIDE_EDGE_COLOR, false);
+            }
+            for &e in report.stretched_edges.iter() {
+                edge_colors[e] = (STRETCHED_COLOR, false);
+            }
+            for &v in report.outside_vertices.iter() {
+                vertex_colors[v] = Some((OUTSIDE_VERTEX_COLOR, true));
+            }
+            for (_, violation) in report.absorbed.iter() {
+                match *violation {
+                    Violation::StretchedEdge(e) => edge_colors[e] = (STRETCHED_COLOR, true),
+                    Violation::OutsideEdge(e) => edge_colors[e] = (OUTSIDE_EDGE_COLOR, true),
+                    Violation::OutsideVertex(v) => {
+                        vertex_colors[v] = Some((OUTSIDE_VERTEX_COLOR, false))
+                    }
+                }
+            }
+
+            for (i, &(v1, v2)) in figure.edges.iter().enumerate() {
+                let (color, dashed) = edge_colors[i];
+                svg += line(
+                    &pose.vertices[v1],
+                    &pose.vertices[v2],
+                    color,
+                    unit * 3.0,
+                    dashed,
+                )
+                .as_str();
+            }
+            for (i, p) in pose.vertices.iter().enumerate() {
+                let title = format!("vertex {} ({}, {})", i, p.x, p.y);
+                svg += match vertex_colors[i] {
+                    Some((color, filled)) => {
+                        circle(p, unit * 6.0, color, filled, unit * 2.0, title.as_str())
+                    }
+                    None => circle(p, unit * 3.0, POSE_COLOR, true, unit, title.as_str()),
+                }
+                .as_str();
+            }
+        } else {
+            // 頂点の数が合わないときは辺を結べないので、頂点だけ描く
+            for p in pose.vertices.iter() {
+                svg += circle(p, unit * 4.0, STRETCHED_COLOR, true, unit, "").as_str();
+            }
+        }
+
+        let status = if report.is_valid() {
+            format!("valid, dislike {}", dislike(problem, pose))
+        } else {
+            "invalid".to_string()
+        };
+        svg += caption(&origin, unit, 0, status.as_str()).as_str();
+    }
+
+    svg += "</svg>\n";
+    svg
+}
+
+// 2 つの pose を重ねて描く。a は青、b は橙で、動いた頂点は灰色の点線で結ぶ
+// hole の外にある頂点は大きい丸にする
+pub fn render_comparison(problem: &Problem, a: &Pose, b: &Pose) -> String {
+    let (mut svg, unit, origin) = begin_svg(problem, &[a, b], None);
+    for (pa, pb) in a.vertices.iter().zip(b.vertices.iter()) {
+        if !pa.eq(pb) {
+            svg += line(pa, pb, DISPLACEMENT_COLOR, unit, true).as_str();
+        }
+    }
+    for (name, pose, color) in [("a", a, POSE_A_COLOR), ("b", b, POSE_B_COLOR)] {
+        let report = validate(problem, pose);
+        if report.vertex_count_matches {
+            for &(v1, v2) in posed_figure(problem, pose).edges.iter() {
+                svg += line(
+                    &pose.vertices[v1],
+                    &pose.vertices[v2],
+                    color,
+                    unit * 3.0,
+                    false,
+                )
+                .as_str();
+            }
+        }
+        for (i, p) in pose.vertices.iter().enumerate() {
+            let outside = report.outside_vertices.contains(&i);
+            let title = format!("{}: vertex {} ({}, {})", name, i, p.x, p.y);
+            let r = if outside { unit * 6.0 } else { unit * 3.0 };
+            svg += circle(p, r, color, !outside, unit * 2.0, title.as_str()).as_str();
+        }
+        let status = if report.is_valid() {
+            format!("{}: valid, dislike {}", name, dislike(problem, pose))
+        } else {
+            format!("{}: invalid, dislike {}", name, dislike(problem, pose))
+        };
+        let line_no = if name == "a" { 0 } else { 1 };
+        svg += caption(&origin, unit, line_no, status.as_str())
+            .replace("<text ", format!("<text fill=\"{}\" ", color).as_str())
+            .as_str();
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+// trajectory から最大 count 枚を選んで、コマ送りで再生する HTML にする
+pub fn render_animation(problem: &Problem, trajectory: &Trajectory, count: usize) -> String {
+    let picked = trajectory.pick(count);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>problem {} (seed {})</title>\n</head>\n<body>\n",
+        trajectory.problem_id, trajectory.seed
+    );
+    html += format!(
+        "<div><button id=\"play\">pause</button> <input id=\"frame\" type=\"range\" min=\"0\" max=\"{}\" value=\"0\"> <span id=\"summary\"></span></div>\n",
+        picked.len().max(1) - 1
+    )
+    .as_str();
+    for (i, &index) in picked.iter().enumerate() {
+        let sample = &trajectory.samples[index];
+        html += format!(
+            "<div class=\"frame\" data-summary=\"{}\" style=\"display: {}\">\n",
+            sample.summary(),
+            if i == 0 { "block" } else { "none" }
+        )
+        .as_str();
+        html += render_svg(problem, Some(&trajectory.pose(index))).as_str();
+        html += "</div>\n";
+    }
+    html += "<script>
+const frames = document.getElementsByClassName('frame');
+const slider = document.getElementById('frame');
+const button = document.getElementById('play');
+const summary = document.getElementById('summary');
+let current = 0;
+let playing = true;
+function show(i) {
+  if (frames.length == 0) return;
+  frames[current].style.display = 'none';
+  current = i;
+  frames[current].style.display = 'block';
+  slider.value = current;
+  summary.textContent = frames[current].dataset.summary;
+}
+slider.oninput = () => show(Number(slider.value));
+button.onclick = () => {
+  playing = !playing;
+  button.textContent = playing ? 'pause' : 'play';
+};
+setInterval(() => {
+  if (playing && frames.length > 0) show((current + 1) % frames.length);
+}, 100);
+show(0);
+</script>
+</body>
+</html>
+";
+    html
+}
+
+#[test]
+fn test_render_svg() {
+    let problem = Problem::from_file(crate::paths::DataDir::workspace().problem(11).as_str());
+    let svg = render_svg(&problem, None);
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches("<line").count(), problem.figure.edges.len());
+
+    // 元の figure のままの pose は hole からはみ出す
+    let mut pose = Pose::new();
+    pose.vertices = problem.figure.vertices.clone();
+    let svg = render_svg(&problem, Some(&pose));
+    assert_eq!(svg.matches("<line").count(), problem.figure.edges.len() * 2);
+    assert!(svg.contains(OUTSIDE_EDGE_COLOR));
+    assert!(svg.contains("invalid"));
+}
+
+#[test]
+fn test_render_animation() {
+    let problem = Problem::from_file(crate::paths::DataDir::workspace().problem(11).as_str());
+    let mut text = Trajectory::header(11, 1, None);
+    for i in 0..5 {
+        text += format!("0 {} 1 0.5 0 0 0 0 10 0 10 10 0 10\n", i * 100).as_str();
+    }
+    let trajectory = Trajectory::parse(text.as_str()).unwrap();
+    let html = render_animation(&problem, &trajectory, 3);
+    assert_eq!(html.matches("<svg").count(), 3);
+    assert!(html.contains("iteration 400"));
+}
+
+#[test]
+fn test_render_comparison() {
+    let problem = Problem::from_file(crate::paths::DataDir::workspace().problem(11).as_str());
+    let mut a = Pose::new();
+    a.vertices = problem.figure.vertices.clone();
+    let mut b = a.clone();
+    b.vertices[0] = Point::new(10.0, 10.0);
+    let svg = render_comparison(&problem, &a, &b);
+    // 元の figure と a と b の辺、動いた頂点 1 つ
+    assert_eq!(
+        svg.matches("<line").count(),
+        problem.figure.edges.len() * 3 + 1
+    );
+    assert!(svg.contains(POSE_A_COLOR) && svg.contains(POSE_B_COLOR));
+    assert!(svg.contains("b: invalid"));
+}
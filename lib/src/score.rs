@@ -0,0 +1,38 @@
+use crate::data::Problem;
+
+// 公式の得点計算
+// score = ceil(1000 * log2(|V| * |E| * |H| / 6) * sqrt((Dbest + 1) / (D + 1)))
+// Dbest は全チームで最も良い dislike だが手元では分からないので、呼び出し側が見積もって渡す
+pub fn score(problem: &Problem, dislike: f64, best_dislike: f64) -> f64 {
+    let v = problem.figure.vertices.len() as f64;
+    let e = problem.figure.edges.len() as f64;
+    let h = problem.hole.vertices.len() as f64;
+    let rate = ((best_dislike + 1.0) / (dislike + 1.0)).sqrt().min(1.0);
+    (1000.0 * (v * e * h / 6.0).log2() * rate).ceil()
+}
+
+// dislike が Dbest と同じときに取れる得点
+pub fn max_score(problem: &Problem) -> f64 {
+    score(problem, 0.0, 0.0)
+}
+
+// まだ取れる得点の見積もり。Dbest は 0 と仮定する
+// 解がない (dislike が None) 問題は満点分が残っている
+pub fn score_potential(problem: &Problem, dislike: Option<f64>) -> f64 {
+    match dislike {
+        Some(dislike) => max_score(problem) - score(problem, dislike, 0.0),
+        None => max_score(problem),
+    }
+}
+
+#[test]
+fn test_score() {
+    let problem = Problem::from_file(crate::paths::DataDir::workspace().problem(1).as_str());
+    // |V| = 20, |E| = 30, |H| = 9
+    let base = 1000.0 * (20.0 * 30.0 * 9.0 / 6.0f64).log2();
+    assert_eq!(max_score(&problem), base.ceil());
+    assert_eq!(score(&problem, 3.0, 0.0), (base * 0.5).ceil());
+    assert_eq!(score(&problem, 3.0, 3.0), base.ceil());
+    assert_eq!(score_potential(&problem, Some(0.0)), 0.0);
+    assert_eq!(score_potential(&problem, None), base.ceil());
+}
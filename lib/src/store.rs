@@ -0,0 +1,479 @@
+use crate::data::Pose;
+use crate::paths::{create_parent, DataDir};
+use serde_json::Value;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// 見つけた valid な解を、誰がどうやって見つけたかと一緒に全て残す
+//   solutions/{id}/{作成時刻 ms}_{pid}_{連番}.json   解
+//   solutions/index.jsonl                              1 行に 1 件。追記だけする
+// index の行
+//   {"type": "solution", "problem": 42, "file": "42/....json", "dislike": 1656, "author": "...",
+//    "solver": "solve2", "seed": 5, "config": "...", "created": 1626000000}
+//   {"type": "submitted", "problem": 42, "file": "42/....json", "created": 1626000000}
+// 複数のプロセスから同時に書けるように、index への追記は solutions/lock を取ってから行う
+
+// 解を見つけた人の名前を指定する環境変数。なければ USER
+pub const AUTHOR_ENV: &str = "ICFPC_AUTHOR";
+
+// この時間より古い lock は、落ちたプロセスが残したものとみなして消す
+const STALE_LOCK_SECS: u64 = 60;
+const LOCK_TIMEOUT_MS: u64 = 30000;
+
+static SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+
+fn now() -> Duration {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
+}
+
+pub fn default_author() -> String {
+    for key in [AUTHOR_ENV, "USER"] {
+        if let Ok(author) = env::var(key) {
+            if !author.is_empty() {
+                return author;
+            }
+        }
+    }
+    "unknown".to_string()
+}
+
+// solver が解と一緒に残した {拡張子を除いたもの}.meta.json の seed
+pub fn read_seed(pose_filepath: &str) -> Option<u64> {
+    let meta_filepath = format!("{}.meta.json", pose_filepath.trim_end_matches(".json"));
+    let text = fs::read_to_string(meta_filepath).ok()?;
+    serde_json::from_str::<Value>(text.as_str()).ok()?["seed"].as_u64()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// 解と一緒に残す情報
+#[derive(Clone, Debug)]
+pub struct SolutionMeta {
+    pub author: String,
+    // solve2, exhaustive, editor など
+    pub solver: String,
+    pub seed: Option<u64>,
+    pub config: String,
+}
+
+impl SolutionMeta {
+    pub fn new(solver: &str, seed: Option<u64>, config: &str) -> SolutionMeta {
+        SolutionMeta {
+            author: default_author(),
+            solver: solver.to_string(),
+            seed,
+            config: config.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SolutionRecord {
+    pub problem_id: usize,
+    // solutions からの相対パス
+    pub file: String,
+    pub dislike: f64,
+    pub meta: SolutionMeta,
+    // unix 時間 (秒)
+    pub created: u64,
+    pub submitted: bool,
+}
+
+impl SolutionRecord {
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"type\": \"solution\", \"problem\": {}, \"file\": \"{}\", \"dislike\": {}, \"author\": \"{}\", \"solver\": \"{}\", \"seed\": {}, \"config\": \"{}\", \"created\": {}}}",
+            self.problem_id,
+            escape(self.file.as_str()),
+            self.dislike,
+            escape(self.meta.author.as_str()),
+            escape(self.meta.solver.as_str()),
+            self.meta
+                .seed
+                .map_or("null".to_string(), |seed| seed.to_string()),
+            escape(self.meta.config.as_str()),
+            self.created
+        )
+    }
+
+    fn from_json(v: &Value) -> Option<SolutionRecord> {
+        Some(SolutionRecord {
+            problem_id: v["problem"].as_u64()? as usize,
+            file: v["file"].as_str()?.to_string(),
+            dislike: v["dislike"].as_f64()?,
+            meta: SolutionMeta {
+                author: v["author"].as_str()?.to_string(),
+                solver: v["solver"].as_str()?.to_string(),
+                seed: v["seed"].as_u64(),
+                config: v["config"].as_str()?.to_string(),
+            },
+            created: v["created"].as_u64()?,
+            submitted: false,
+        })
+    }
+}
+
+// solutions/lock。中身は取ったプロセスごとに違う token ({pid}_{連番})。drop で外す
+struct Lock {
+    filepath: String,
+    token: String,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // 古いとみなされて他のプロセスに取られていたら、その lock は消さない
+        if fs::read_to_string(self.filepath.as_str()).is_ok_and(|t| t == self.token) {
+            let _ = fs::remove_file(self.filepath.as_str());
+        }
+    }
+}
+
+// 古い lock を消す。見たときから他のプロセスが lock を取り直していたら、元に戻す
+// 消す前に別名に移すので、他のプロセスが新しく作った lock を、中身を確かめずに消すことはない
+fn remove_stale_lock(filepath: &str, observed: &str, token: &str) {
+    let moved = format!("{}.stale_{}", filepath, token);
+    if fs::rename(filepath, moved.as_str()).is_err() {
+        // 他のプロセスが先に消した
+        return;
+    }
+    if fs::read_to_string(moved.as_str()).is_ok_and(|t| t != observed) {
+        // 新しい lock だったので戻す。その間にさらに別の lock ができていれば、そちらを残す
+        let _ = fs::hard_link(moved.as_str(), filepath);
+    }
+    let _ = fs::remove_file(moved.as_str());
+}
+
+pub struct SolutionStore {
+    root: String,
+}
+
+impl SolutionStore {
+    pub fn new(data: &DataDir) -> SolutionStore {
+        SolutionStore {
+            root: data.solutions_dir(),
+        }
+    }
+
+    fn index_filepath(&self) -> String {
+        format!("{}/index.jsonl", self.root)
+    }
+
+    pub fn filepath(&self, record: &SolutionRecord) -> String {
+        format!("{}/{}", self.root, record.file)
+    }
+
+    pub fn pose(&self, record: &SolutionRecord) -> Pose {
+        Pose::from_file(self.filepath(record).as_str())
+    }
+
+    fn lock(&self) -> Result<Lock, String> {
+        let filepath = format!("{}/lock", self.root);
+        create_parent(filepath.as_str())?;
+        let token = format!(
+            "{}_{}",
+            process::id(),
+            SEQUENCE.fetch_add(1, Ordering::SeqCst)
+        );
+        let start = now();
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(filepath.as_str())
+            {
+                Ok(mut file) => {
+                    let _ = write!(file, "{}", token);
+                    return Ok(Lock { filepath, token });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    let age = fs::metadata(filepath.as_str())
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|t| t.elapsed().ok());
+                    if age.is_some_and(|age| age.as_secs() >= STALE_LOCK_SECS) {
+                        let observed = fs::read_to_string(filepath.as_str()).unwrap_or_default();
+                        remove_stale_lock(filepath.as_str(), observed.as_str(), token.as_str());
+                        continue;
+                    }
+                    if (now() - start).as_millis() as u64 >= LOCK_TIMEOUT_MS {
+                        return Err(format!("timeout waiting for {}", filepath));
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => return Err(format!("{}: {}", filepath, e)),
+            }
+        }
+    }
+
+    // lock を取って f を実行する。best の読み比べと書き込みを他のプロセスと混ぜないときに使う
+    pub fn with_lock<T, F: FnOnce() -> T>(&self, f: F) -> Result<T, String> {
+        let _lock = self.lock()?;
+        Ok(f())
+    }
+
+    fn append(&self, line: &str) -> Result<(), String> {
+        let _lock = self.lock()?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.index_filepath())
+            .map_err(|e| e.to_string())?;
+        file.write_all(format!("{}\n", line).as_bytes())
+            .map_err(|e| e.to_string())
+    }
+
+    // 解を保存して index に加える。呼ぶ側で valid なことを確かめておく
+    pub fn add(
+        &self,
+        problem_id: usize,
+        pose: &Pose,
+        dislike: f64,
+        meta: &SolutionMeta,
+    ) -> Result<SolutionRecord, String> {
+        let dir = format!("{}/{}", self.root, problem_id);
+        fs::create_dir_all(dir.as_str()).map_err(|e| e.to_string())?;
+        let created = now();
+        let file = format!(
+            "{}/{}_{}_{}.json",
+            problem_id,
+            created.as_millis(),
+            process::id(),
+            SEQUENCE.fetch_add(1, Ordering::SeqCst)
+        );
+        // 途中まで書いたファイルを読まれないように、別名で書いてから移す
+        let filepath = format!("{}/{}", self.root, file);
+        let tmp_filepath = format!("{}.tmp", filepath);
+        fs::write(tmp_filepath.as_str(), pose.to_json()).map_err(|e| e.to_string())?;
+        fs::rename(tmp_filepath.as_str(), filepath.as_str()).map_err(|e| e.to_string())?;
+
+        let record = SolutionRecord {
+            problem_id,
+            file,
+            dislike,
+            meta: meta.clone(),
+            created: created.as_secs(),
+            submitted: false,
+        };
+        self.append(record.to_json().as_str())?;
+        Ok(record)
+    }
+
+    pub fn mark_submitted(&self, record: &SolutionRecord) -> Result<(), String> {
+        self.append(
+            format!(
+                "{{\"type\": \"submitted\", \"problem\": {}, \"file\": \"{}\", \"created\": {}}}",
+                record.problem_id,
+                escape(record.file.as_str()),
+                now().as_secs()
+            )
+            .as_str(),
+        )
+    }
+
+    // 同じ pose の記録。なければ None
+    pub fn find(&self, problem_id: usize, pose: &Pose) -> Option<SolutionRecord> {
+        let json = pose.to_json();
+        self.records()
+            .into_iter()
+            .filter(|r| r.problem_id == problem_id)
+            .find(|r| fs::read_to_string(self.filepath(r)).is_ok_and(|s| s == json))
+    }
+
+    // 提出した pose を提出済みにする。store になければ由来の分からない解として加える
+    pub fn mark_pose_submitted(
+        &self,
+        problem_id: usize,
+        pose: &Pose,
+        dislike: f64,
+    ) -> Result<(), String> {
+        let record = match self.find(problem_id, pose) {
+            Some(record) => record,
+            None => self.add(
+                problem_id,
+                pose,
+                dislike,
+                &SolutionMeta::new("unknown", None, ""),
+            )?,
+        };
+        self.mark_submitted(&record)
+    }
+
+    // 全ての記録を古い順に。submitted は後の行から反映する
+    // 書きかけや壊れた行は読み飛ばす
+    pub fn records(&self) -> Vec<SolutionRecord> {
+        let text = fs::read_to_string(self.index_filepath()).unwrap_or_default();
+        let mut records: Vec<SolutionRecord> = vec![];
+        for line in text.lines() {
+            let v = match serde_json::from_str::<Value>(line) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+            match v["type"].as_str() {
+                Some("solution") => {
+                    if let Some(record) = SolutionRecord::from_json(&v) {
+                        records.push(record);
+                    }
+                }
+                Some("submitted") => {
+                    let file = v["file"].as_str().unwrap_or("");
+                    for record in records.iter_mut().filter(|r| r.file == file) {
+                        record.submitted = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        records
+    }
+
+    // 問題ごとに dislike が最小の記録 (同じなら先に見つけたもの)。問題番号の順
+    pub fn best_per_problem(&self) -> Vec<SolutionRecord> {
+        let mut best: Vec<SolutionRecord> = vec![];
+        for record in self.records() {
+            match best.iter_mut().find(|b| b.problem_id == record.problem_id) {
+                Some(b) => {
+                    if record.dislike < b.dislike {
+                        *b = record;
+                    }
+                }
+                None => best.push(record),
+            }
+        }
+        best.sort_by_key(|r| r.problem_id);
+        best
+    }
+
+    // 提出済みのものより dislike が小さく、まだ提出していない最良の記録
+    pub fn unsubmitted_improvements(&self) -> Vec<SolutionRecord> {
+        let records = self.records();
+        self.best_per_problem()
+            .into_iter()
+            .filter(|best| {
+                !best.submitted
+                    && records
+                        .iter()
+                        .filter(|r| r.problem_id == best.problem_id && r.submitted)
+                        .all(|r| best.dislike < r.dislike)
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_solution_store() {
+    use crate::data::Point;
+
+    let root = env::temp_dir().join(format!("solution_store_test_{}", process::id()));
+    let data = DataDir::new(root.to_str().unwrap());
+    let store = SolutionStore::new(&data);
+    let meta = SolutionMeta::new("solve2", Some(5), "step=0.8");
+    let mut pose = Pose::new();
+    pose.push(Point::new(1.0, 2.0));
+
+    let a = store.add(1, &pose, 100.0, &meta).unwrap();
+    store.add(1, &pose, 80.0, &meta).unwrap();
+    let c = store
+        .add(2, &pose, 10.0, &SolutionMeta::new("editor", None, ""))
+        .unwrap();
+    assert_ne!(a.file, c.file);
+    assert_eq!(store.pose(&a).vertices.len(), 1);
+
+    let best = store.best_per_problem();
+    assert_eq!(
+        best.iter()
+            .map(|r| (r.problem_id, r.dislike))
+            .collect::<Vec<(usize, f64)>>(),
+        vec![(1, 80.0), (2, 10.0)]
+    );
+    assert_eq!(best[0].meta.seed, Some(5));
+    assert_eq!(best[1].meta.seed, None);
+    assert_eq!(store.unsubmitted_improvements().len(), 2);
+
+    store.mark_submitted(&c).unwrap();
+    let improvements = store.unsubmitted_improvements();
+    assert_eq!(improvements.len(), 1);
+    assert_eq!(improvements[0].problem_id, 1);
+    assert!(store.find(2, &pose).unwrap().submitted);
+    store.mark_pose_submitted(3, &pose, 5.0).unwrap();
+    assert!(store.find(3, &pose).unwrap().submitted);
+
+    // 書きかけの行は読み飛ばす
+    let mut index = OpenOptions::new()
+        .append(true)
+        .open(store.index_filepath())
+        .unwrap();
+    write!(index, "{{\"type\": \"solution\", \"prob").unwrap();
+    assert_eq!(store.records().len(), 4);
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_concurrent_add() {
+    use crate::data::Point;
+
+    let root = env::temp_dir().join(format!("solution_store_concurrent_{}", process::id()));
+    let data = DataDir::new(root.to_str().unwrap());
+    let handles = (0..4)
+        .map(|t| {
+            let data = data.clone();
+            thread::spawn(move || {
+                let store = SolutionStore::new(&data);
+                let mut pose = Pose::new();
+                pose.push(Point::new(t as f64, 0.0));
+                for i in 0..25 {
+                    store
+                        .add(t, &pose, i as f64, &SolutionMeta::new("test", None, ""))
+                        .unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let store = SolutionStore::new(&data);
+    assert_eq!(store.records().len(), 100);
+    assert!(!std::path::Path::new(format!("{}/lock", store.root).as_str()).exists());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_stale_lock() {
+    let root = env::temp_dir().join(format!("solution_store_stale_{}", process::id()));
+    let data = DataDir::new(root.to_str().unwrap());
+    let store = SolutionStore::new(&data);
+    let filepath = format!("{}/lock", store.root);
+    fs::create_dir_all(store.root.as_str()).unwrap();
+
+    // 落ちたプロセスが残した古い lock は取り直せる
+    fs::write(filepath.as_str(), "1_0").unwrap();
+    let old = SystemTime::now() - Duration::from_secs(STALE_LOCK_SECS + 1);
+    fs::File::options()
+        .write(true)
+        .open(filepath.as_str())
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+    assert_eq!(store.with_lock(|| 1), Ok(1));
+    assert!(!std::path::Path::new(filepath.as_str()).exists());
+
+    // 古い lock を見た後に他のプロセスが取り直していたら、その lock は残す
+    fs::write(filepath.as_str(), "2_0").unwrap();
+    remove_stale_lock(filepath.as_str(), "1_0", "3_0");
+    assert_eq!(fs::read_to_string(filepath.as_str()).unwrap(), "2_0");
+
+    // 自分の lock が他のプロセスに取られていたら、drop で消さない
+    drop(Lock {
+        filepath: filepath.clone(),
+        token: "3_0".to_string(),
+    });
+    assert_eq!(fs::read_to_string(filepath.as_str()).unwrap(), "2_0");
+    fs::remove_dir_all(root).unwrap();
+}
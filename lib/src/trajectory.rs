@@ -0,0 +1,238 @@
+use crate::data::{BonusType, Point, Pose, UsedBonus};
+use std::fs;
+
+// 焼きなましの途中経過を記録したファイル
+// 1 行目: trajectory <problem_id> <seed>
+// bonus を使っていれば: bonus <名前> <problem> [<v1> <v2>]
+// 以降 1 行に 1 つ:
+//   <chain> <iteration> <temperature> <eval> <dislike> <p0> <p1> <p2> <x0> <y0> <x1> <y1> ...
+// chain は初期解ごとの焼きなましの番号、iteration はその中での反復回数
+
+#[derive(Clone, Debug)]
+pub struct TrajectorySample {
+    pub chain: usize,
+    pub iteration: usize,
+    pub temperature: f64,
+    pub eval: f64,
+    pub dislike: f64,
+    // 穴の外への距離、辺の長さ、辺のはみ出し (solver の penalty)
+    pub penalty: (f64, f64, f64),
+    pub vertices: Vec<Point>,
+}
+
+impl TrajectorySample {
+    pub fn to_line(&self) -> String {
+        let mut line = format!(
+            "{} {} {} {} {} {} {} {}",
+            self.chain,
+            self.iteration,
+            self.temperature,
+            self.eval,
+            self.dislike,
+            self.penalty.0,
+            self.penalty.1,
+            self.penalty.2
+        );
+        for p in self.vertices.iter() {
+            line += format!(" {} {}", p.x, p.y).as_str();
+        }
+        line
+    }
+
+    fn from_line(line: &str) -> Result<TrajectorySample, String> {
+        let tokens = line.split_whitespace().collect::<Vec<&str>>();
+        if tokens.len() < 8 || tokens.len() % 2 != 0 {
+            return Err(format!("invalid trajectory sample: {}", line));
+        }
+        let int = |s: &str| {
+            s.parse::<usize>()
+                .map_err(|_| format!("invalid trajectory sample: {}", line))
+        };
+        let float = |s: &str| {
+            s.parse::<f64>()
+                .map_err(|_| format!("invalid trajectory sample: {}", line))
+        };
+        let mut vertices = vec![];
+        for xy in tokens[8..].chunks(2) {
+            vertices.push(Point::new(float(xy[0])?, float(xy[1])?));
+        }
+        Ok(TrajectorySample {
+            chain: int(tokens[0])?,
+            iteration: int(tokens[1])?,
+            temperature: float(tokens[2])?,
+            eval: float(tokens[3])?,
+            dislike: float(tokens[4])?,
+            penalty: (float(tokens[5])?, float(tokens[6])?, float(tokens[7])?),
+            vertices,
+        })
+    }
+
+    // 画面に出す 1 行の説明
+    pub fn summary(&self) -> String {
+        format!(
+            "chain {}, iteration {}, temperature {:.3}, eval {:.4}, dislike {}, penalty {} {} {}",
+            self.chain,
+            self.iteration,
+            self.temperature,
+            self.eval,
+            self.dislike,
+            self.penalty.0,
+            self.penalty.1,
+            self.penalty.2
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Trajectory {
+    pub problem_id: usize,
+    pub seed: u64,
+    pub bonus: Option<UsedBonus>,
+    pub samples: Vec<TrajectorySample>,
+}
+
+impl Trajectory {
+    // サンプルより前に書く部分
+    pub fn header(problem_id: usize, seed: u64, bonus: Option<&UsedBonus>) -> String {
+        let mut header = format!("trajectory {} {}\n", problem_id, seed);
+        if let Some(bonus) = bonus {
+            header += format!("bonus {} {}", bonus.bonus.as_str(), bonus.problem).as_str();
+            if let Some((v1, v2)) = bonus.edge {
+                header += format!(" {} {}", v1, v2).as_str();
+            }
+            header += "\n";
+        }
+        header
+    }
+
+    pub fn parse(text: &str) -> Result<Trajectory, String> {
+        let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+        let header = lines.next().ok_or("empty trajectory")?;
+        let tokens = header.split_whitespace().collect::<Vec<&str>>();
+        let (problem_id, seed) = match tokens.as_slice() {
+            ["trajectory", id, seed] => (
+                id.parse::<usize>().map_err(|_| "invalid problem id")?,
+                seed.parse::<u64>().map_err(|_| "invalid seed")?,
+            ),
+            _ => return Err(format!("invalid trajectory header: {}", header)),
+        };
+        let mut trajectory = Trajectory {
+            problem_id,
+            seed,
+            bonus: None,
+            samples: vec![],
+        };
+        for line in lines {
+            if let Some(rest) = line.strip_prefix("bonus ") {
+                trajectory.bonus = Some(parse_bonus(rest)?);
+            } else {
+                trajectory.samples.push(TrajectorySample::from_line(line)?);
+            }
+        }
+        Ok(trajectory)
+    }
+
+    pub fn from_file(filepath: &str) -> Result<Trajectory, String> {
+        let text = fs::read_to_string(filepath).map_err(|e| format!("{}: {}", filepath, e))?;
+        Trajectory::parse(text.as_str())
+    }
+
+    // index 番目のサンプルの pose。bonus の宣言も付ける
+    pub fn pose(&self, index: usize) -> Pose {
+        let mut pose = Pose::new();
+        pose.vertices = self.samples[index].vertices.clone();
+        if let Some(bonus) = &self.bonus {
+            pose.bonuses.push(*bonus);
+        }
+        pose
+    }
+
+    // 最初と最後を含めて、最大 count 個のサンプルを等間隔に選ぶ
+    pub fn pick(&self, count: usize) -> Vec<usize> {
+        let n = self.samples.len();
+        if n <= count {
+            return (0..n).collect();
+        }
+        if count <= 1 {
+            return vec![n - 1];
+        }
+        let mut picked = (0..count)
+            .map(|i| i * (n - 1) / (count - 1))
+            .collect::<Vec<usize>>();
+        picked.dedup();
+        picked
+    }
+}
+
+fn parse_bonus(s: &str) -> Result<UsedBonus, String> {
+    let tokens = s.split_whitespace().collect::<Vec<&str>>();
+    let invalid = || format!("invalid trajectory bonus: {}", s);
+    let bonus = tokens
+        .first()
+        .and_then(|name| BonusType::from_name(name))
+        .ok_or_else(invalid)?;
+    let int = |i: usize| tokens[i].parse::<usize>().map_err(|_| invalid());
+    let (problem, edge) = match tokens.len() {
+        2 => (int(1)?, None),
+        4 => (int(1)?, Some((int(2)?, int(3)?))),
+        _ => return Err(invalid()),
+    };
+    Ok(UsedBonus {
+        bonus,
+        problem,
+        edge,
+    })
+}
+
+#[test]
+fn test_trajectory() {
+    let bonus = UsedBonus {
+        bonus: BonusType::BreakALeg,
+        problem: 3,
+        edge: Some((0, 2)),
+    };
+    let sample = TrajectorySample {
+        chain: 0,
+        iteration: 1000,
+        temperature: 1.5,
+        eval: 0.25,
+        dislike: 120.0,
+        penalty: (0.0, 2.5, 0.0),
+        vertices: vec![Point::new(1.0, 2.0), Point::new(3.0, 4.0)],
+    };
+    let text = Trajectory::header(7, 42, Some(&bonus)) + sample.to_line().as_str() + "\n";
+    assert_eq!(
+        text,
+        "trajectory 7 42\nbonus BREAK_A_LEG 3 0 2\n0 1000 1.5 0.25 120 0 2.5 0 1 2 3 4\n"
+    );
+
+    let trajectory = Trajectory::parse(text.as_str()).unwrap();
+    assert_eq!(trajectory.problem_id, 7);
+    assert_eq!(trajectory.seed, 42);
+    assert_eq!(trajectory.samples.len(), 1);
+    assert_eq!(trajectory.samples[0].penalty, (0.0, 2.5, 0.0));
+    let pose = trajectory.pose(0);
+    assert_eq!(pose.vertices.len(), 2);
+    assert_eq!(pose.bonuses[0].edge, Some((0, 2)));
+
+    assert!(Trajectory::parse("trajectory 7\n").is_err());
+    assert!(Trajectory::parse("trajectory 7 42\n0 1 2\n").is_err());
+}
+
+#[test]
+fn test_pick() {
+    let mut trajectory = Trajectory::parse("trajectory 1 1\n").unwrap();
+    for i in 0..10 {
+        trajectory.samples.push(TrajectorySample {
+            chain: 0,
+            iteration: i,
+            temperature: 1.0,
+            eval: 0.0,
+            dislike: 0.0,
+            penalty: (0.0, 0.0, 0.0),
+            vertices: vec![],
+        });
+    }
+    assert_eq!(trajectory.pick(4), vec![0, 3, 6, 9]);
+    assert_eq!(trajectory.pick(20).len(), 10);
+}
@@ -0,0 +1,549 @@
+use crate::data::{Bonus, BonusType, Figure, Hole, Line, Point, Pose, Problem};
+
+// 公式ルールに従って pose を検証する
+
+// 点が hole の内部か境界上にあるか
+pub fn is_inside_hole(hole: &Hole, p: &Point) -> bool {
+    let n = hole.vertices.len();
+    let mut inside = false;
+    for i in 0..n {
+        let a = hole.vertices[i];
+        let b = hole.vertices[(i + 1) % n];
+        // 境界上
+        if Point::ccw(&a, &b, p) == 0 {
+            return true;
+        }
+        // p から x 軸正方向に伸ばした半直線との交差判定
+        if (a.y > p.y) != (b.y > p.y) {
+            let x = a.x + (p.y - a.y) * (b.x - a.x) / (b.y - a.y);
+            if p.x < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+#[test]
+fn test_is_inside_hole() {
+    let mut hole = Hole::new();
+    hole.push(Point::new(0.0, 0.0));
+    hole.push(Point::new(4.0, 0.0));
+    hole.push(Point::new(4.0, 4.0));
+    hole.push(Point::new(2.0, 2.0));
+    hole.push(Point::new(0.0, 4.0));
+    assert!(is_inside_hole(&hole, &Point::new(1.0, 1.0)));
+    assert!(is_inside_hole(&hole, &Point::new(0.0, 2.0)));
+    assert!(is_inside_hole(&hole, &Point::new(2.0, 2.0)));
+    assert!(is_inside_hole(&hole, &Point::new(4.0, 4.0)));
+    assert!(!is_inside_hole(&hole, &Point::new(2.0, 3.0)));
+    assert!(!is_inside_hole(&hole, &Point::new(5.0, 1.0)));
+}
+
+// 線分 p0-p1 の全ての点が hole の内部か境界上にあるか
+pub fn is_segment_inside_hole(hole: &Hole, p0: &Point, p1: &Point) -> bool {
+    if !is_inside_hole(hole, p0) || !is_inside_hole(hole, p1) {
+        return false;
+    }
+    let n = hole.vertices.len();
+    let segment = Line::new(*p0, *p1);
+
+    // 線分上に乗っている hole の頂点で線分を分割する
+    let mut splits = vec![0.0, 1.0];
+    let d = *p1 - *p0;
+    for i in 0..n {
+        let a = hole.vertices[i];
+        let b = hole.vertices[(i + 1) % n];
+        // 辺を真に横切っていれば外に出ている
+        if segment.intersect_without_edge(&Line::new(a, b)) {
+            return false;
+        }
+        if Point::ccw(p0, p1, &a) == 0 && d.norm() > 0.0 {
+            splits.push((a - *p0).dot(&d) / d.dot(&d));
+        }
+    }
+    splits.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    // 分割した各区間の中点が内部にあれば、区間全体が内部にある
+    for i in 0..splits.len() - 1 {
+        if splits[i + 1] - splits[i] <= 0.0 {
+            continue;
+        }
+        let t = (splits[i] + splits[i + 1]) / 2.0;
+        if !is_inside_hole(hole, &(*p0 + d * t)) {
+            return false;
+        }
+    }
+    true
+}
+
+#[test]
+fn test_is_segment_inside_hole() {
+    let mut hole = Hole::new();
+    hole.push(Point::new(0.0, 0.0));
+    hole.push(Point::new(4.0, 0.0));
+    hole.push(Point::new(4.0, 4.0));
+    hole.push(Point::new(2.0, 2.0));
+    hole.push(Point::new(0.0, 4.0));
+    // 内部
+    assert!(is_segment_inside_hole(
+        &hole,
+        &Point::new(1.0, 1.0),
+        &Point::new(3.0, 1.0)
+    ));
+    // 境界に沿っている
+    assert!(is_segment_inside_hole(
+        &hole,
+        &Point::new(0.0, 0.0),
+        &Point::new(0.0, 4.0)
+    ));
+    // 凹んだ頂点に接している
+    assert!(is_segment_inside_hole(
+        &hole,
+        &Point::new(0.0, 2.0),
+        &Point::new(4.0, 2.0)
+    ));
+    // 凹んだ部分をまたいでいる
+    assert!(!is_segment_inside_hole(
+        &hole,
+        &Point::new(0.0, 4.0),
+        &Point::new(4.0, 4.0)
+    ));
+    assert!(!is_segment_inside_hole(
+        &hole,
+        &Point::new(1.0, 3.0),
+        &Point::new(3.0, 3.0)
+    ));
+}
+
+// 辺の長さの変化が epsilon の範囲に収まっているか
+pub fn is_acceptable_length(problem: &Problem, orig_dist: f64, cur_dist: f64) -> bool {
+    is_within_epsilon(problem.epsilon, orig_dist, cur_dist)
+}
+
+// |cur_dist / orig_dist - 1| <= epsilon
+// 座標が整数なので、epsilon を元の整数に戻して誤差なく比較する
+// solver は bonus に合わせて広げた epsilon でも使う
+pub fn is_within_epsilon(epsilon: f64, orig_dist: f64, cur_dist: f64) -> bool {
+    let epsilon = (epsilon * 1e6).round();
+    ((cur_dist - orig_dist).abs() * 1e6).round() <= (epsilon * orig_dist).round()
+}
+
+pub fn is_edge_acceptable(problem: &Problem, pose: &Pose, index: usize) -> bool {
+    let (v1, v2) = problem.figure.edges[index];
+    let orig_dist = problem.figure.vertices[v1].distance2(&problem.figure.vertices[v2]);
+    let cur_dist = pose.vertices[v1].distance2(&pose.vertices[v2]);
+    is_acceptable_length(problem, orig_dist, cur_dist)
+}
+
+pub fn is_edge_inside_hole(problem: &Problem, pose: &Pose, index: usize) -> bool {
+    let (v1, v2) = problem.figure.edges[index];
+    is_segment_inside_hole(&problem.hole, &pose.vertices[v1], &pose.vertices[v2])
+}
+
+// pose が BREAK_A_LEG を使っていれば辺を折った figure。ValidationReport の辺の番号はこれに対応する
+pub fn posed_figure(problem: &Problem, pose: &Pose) -> Figure {
+    let mut figure = problem.figure.clone();
+    for bonus in pose.bonuses.iter() {
+        if let (BonusType::BreakALeg, Some(edge)) = (bonus.bonus, bonus.edge) {
+            figure.split_edge(edge.0, edge.1);
+        }
+    }
+    figure
+}
+
+pub fn dislike(problem: &Problem, pose: &Pose) -> f64 {
+    let mut sum = 0.0;
+    for hv in problem.hole.vertices.iter() {
+        let mut dist = f64::MAX;
+        for pv in pose.vertices.iter() {
+            dist = dist.min(pv.distance2(hv));
+        }
+        sum += dist;
+    }
+    sum
+}
+
+// bonus はどれかの頂点がちょうど position に乗っていれば取れる
+pub fn is_bonus_collected(pose: &Pose, bonus: &Bonus) -> bool {
+    pose.vertices.iter().any(|p| p.eq(&bonus.position))
+}
+
+pub fn collected_bonuses(problem: &Problem, pose: &Pose) -> Vec<Bonus> {
+    problem
+        .bonuses
+        .iter()
+        .filter(|b| is_bonus_collected(pose, b))
+        .copied()
+        .collect()
+}
+
+#[test]
+fn test_collected_bonuses() {
+    let problem = Problem::from_file(crate::paths::DataDir::workspace().problem(1).as_str());
+    let mut pose = Pose::new();
+    pose.push(Point::new(62.0, 46.0));
+    pose.push(Point::new(23.0, 67.0));
+    let collected = collected_bonuses(&problem, &pose);
+    assert_eq!(collected.len(), 1);
+    assert_eq!(collected[0].bonus, BonusType::Globalist);
+}
+
+// bonus によって許された違反
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Violation {
+    StretchedEdge(usize),
+    OutsideEdge(usize),
+    OutsideVertex(usize),
+}
+
+// 辺や頂点の番号は、BREAK_A_LEG で辺を折った後の figure のもの
+pub struct ValidationReport {
+    // 長さが epsilon の範囲に収まっていない辺
+    pub stretched_edges: Vec<usize>,
+    // hole からはみ出している辺
+    pub outside_edges: Vec<usize>,
+    // hole の外にある頂点
+    pub outside_vertices: Vec<usize>,
+    pub vertex_count_matches: bool,
+    // bonus が吸収した違反。上の 3 つからは取り除かれている
+    pub absorbed: Vec<(BonusType, Violation)>,
+    // bonus の使い方自体の誤り
+    pub bonus_errors: Vec<String>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.vertex_count_matches
+            && self.stretched_edges.is_empty()
+            && self.outside_edges.is_empty()
+            && self.outside_vertices.is_empty()
+            && self.bonus_errors.is_empty()
+    }
+}
+
+// GLOBALIST で使う、全ての辺の |d' / d - 1| の和
+pub fn total_stretch(problem: &Problem, pose: &Pose) -> f64 {
+    let mut sum = 0.0;
+    for &(v1, v2) in problem.figure.edges.iter() {
+        let orig_dist = problem.figure.vertices[v1].distance2(&problem.figure.vertices[v2]);
+        let cur_dist = pose.vertices[v1].distance2(&pose.vertices[v2]);
+        sum += (cur_dist / orig_dist - 1.0).abs();
+    }
+    sum
+}
+
+// GLOBALIST で許される伸び縮みの合計 |E| * epsilon
+pub fn stretch_budget(problem: &Problem) -> f64 {
+    problem.figure.edges.len() as f64 * problem.epsilon
+}
+
+fn find_violations(problem: &Problem, pose: &Pose, report: &mut ValidationReport) {
+    for (i, p) in pose.vertices.iter().enumerate() {
+        if !is_inside_hole(&problem.hole, p) {
+            report.outside_vertices.push(i);
+        }
+    }
+    for i in 0..problem.figure.edges.len() {
+        if !is_edge_acceptable(problem, pose, i) {
+            report.stretched_edges.push(i);
+        }
+        if !is_edge_inside_hole(problem, pose, i) {
+            report.outside_edges.push(i);
+        }
+    }
+}
+
+fn apply_globalist(problem: &Problem, pose: &Pose, report: &mut ValidationReport) {
+    let total = total_stretch(problem, pose);
+    let budget = stretch_budget(problem);
+    if total > budget {
+        report.bonus_errors.push(format!(
+            "GLOBALIST: total stretch {} exceeds {}",
+            total, budget
+        ));
+        return;
+    }
+    for i in report.stretched_edges.drain(..) {
+        report
+            .absorbed
+            .push((BonusType::Globalist, Violation::StretchedEdge(i)));
+    }
+}
+
+fn apply_superflex(report: &mut ValidationReport) {
+    if report.stretched_edges.len() == 1 {
+        let i = report.stretched_edges.pop().unwrap();
+        report
+            .absorbed
+            .push((BonusType::Superflex, Violation::StretchedEdge(i)));
+    }
+}
+
+// WALLHACK で外に出してよい頂点。hole の外にある頂点のうち、最も外れているもの
+// どの頂点も hole の中にあれば None で、はみ出した辺は許さない (solver のペナルティも同じ規則)
+pub fn wallhack_vertex<T: Ord + Default>(n: usize, outside: impl Fn(usize) -> T) -> Option<usize> {
+    (0..n)
+        .filter(|&v| outside(v) > T::default())
+        .max_by_key(|&v| outside(v))
+}
+
+// WALLHACK: 1 頂点だけ hole の外に出てよく、その頂点につながる辺もはみ出してよい
+fn apply_wallhack(problem: &Problem, report: &mut ValidationReport) {
+    let edges = &problem.figure.edges;
+    if report.outside_vertices.len() > 1 {
+        return;
+    }
+    let vertex = match wallhack_vertex(problem.figure.vertices.len(), |v| {
+        report.outside_vertices.contains(&v)
+    }) {
+        Some(v) => v,
+        None => return,
+    };
+    if !report
+        .outside_edges
+        .iter()
+        .all(|&i| edges[i].0 == vertex || edges[i].1 == vertex)
+    {
+        return;
+    }
+    for v in report.outside_vertices.drain(..) {
+        report
+            .absorbed
+            .push((BonusType::Wallhack, Violation::OutsideVertex(v)));
+    }
+    for i in report.outside_edges.drain(..) {
+        report
+            .absorbed
+            .push((BonusType::Wallhack, Violation::OutsideEdge(i)));
+    }
+}
+
+// pose で宣言された bonus のルールで検証する
+pub fn validate(problem: &Problem, pose: &Pose) -> ValidationReport {
+    let mut report = ValidationReport {
+        stretched_edges: vec![],
+        outside_edges: vec![],
+        outside_vertices: vec![],
+        vertex_count_matches: true,
+        absorbed: vec![],
+        bonus_errors: vec![],
+    };
+    if pose.bonuses.len() > 1 {
+        report.bonus_errors.push(format!(
+            "only one bonus can be used, {} given",
+            pose.bonuses.len()
+        ));
+    }
+
+    let mut broken = None;
+    for bonus in pose.bonuses.iter() {
+        if bonus.bonus != BonusType::BreakALeg {
+            continue;
+        }
+        match bonus.edge {
+            Some(edge) => {
+                let mut p = problem.clone();
+                if p.figure.split_edge(edge.0, edge.1).is_some() {
+                    broken = Some(p);
+                } else {
+                    report.bonus_errors.push(format!(
+                        "BREAK_A_LEG: ({}, {}) is not an edge of the figure",
+                        edge.0, edge.1
+                    ));
+                }
+            }
+            None => report
+                .bonus_errors
+                .push("BREAK_A_LEG: no edge is given".to_string()),
+        }
+    }
+    let problem = broken.as_ref().unwrap_or(problem);
+
+    report.vertex_count_matches = problem.figure.vertices.len() == pose.vertices.len();
+    if !report.vertex_count_matches {
+        return report;
+    }
+    find_violations(problem, pose, &mut report);
+
+    for bonus in pose.bonuses.iter() {
+        match bonus.bonus {
+            BonusType::Globalist => apply_globalist(problem, pose, &mut report),
+            BonusType::Superflex => apply_superflex(&mut report),
+            BonusType::Wallhack => apply_wallhack(problem, &mut report),
+            BonusType::BreakALeg => {}
+        }
+    }
+    report
+}
+
+#[test]
+fn test_validate() {
+    let problem = Problem::from_file(crate::paths::DataDir::workspace().problem(11).as_str());
+    let pose = Pose::from_file(crate::paths::DataDir::workspace().best(11).as_str());
+    let report = validate(&problem, &pose);
+    assert!(report.is_valid());
+
+    let mut pose = Pose::from_file(crate::paths::DataDir::workspace().best(11).as_str());
+    pose.vertices[0].x += 100.0;
+    let report = validate(&problem, &pose);
+    assert!(!report.is_valid());
+    assert_eq!(report.outside_vertices, vec![0]);
+}
+
+#[test]
+fn test_is_acceptable_length() {
+    let mut problem = Problem::new();
+    problem.epsilon = 150000.0 / 1e6;
+    // |d' / d - 1| <= 0.15 の境界ちょうど
+    assert!(is_acceptable_length(&problem, 100.0, 115.0));
+    assert!(is_acceptable_length(&problem, 100.0, 85.0));
+    assert!(!is_acceptable_length(&problem, 100.0, 116.0));
+}
+
+#[test]
+fn test_dislike() {
+    let problem = Problem::from_file(crate::paths::DataDir::workspace().problem(11).as_str());
+    let mut pose = Pose::new();
+    for p in problem.hole.vertices.iter() {
+        pose.push(*p);
+    }
+    assert_eq!(dislike(&problem, &pose), 0.0);
+}
+
+#[cfg(test)]
+fn bonus_problem() -> Problem {
+    // 10x10 の正方形の穴に、長さ 4 の辺が 2 本
+    let mut problem = Problem::new();
+    for &(x, y) in [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)].iter() {
+        problem.hole.push(Point::new(x, y));
+    }
+    for &(x, y) in [(1.0, 1.0), (5.0, 1.0), (5.0, 5.0)].iter() {
+        problem.figure.push(Point::new(x, y));
+    }
+    problem.figure.connect(0, 1);
+    problem.figure.connect(1, 2);
+    problem.epsilon = 150000.0 / 1e6;
+    problem
+}
+
+#[cfg(test)]
+fn bonus_pose(vertices: &[(f64, f64)], bonus: BonusType, edge: Option<(usize, usize)>) -> Pose {
+    use crate::data::UsedBonus;
+    let mut pose = Pose::new();
+    for &(x, y) in vertices.iter() {
+        pose.push(Point::new(x, y));
+    }
+    pose.bonuses.push(UsedBonus {
+        bonus,
+        problem: 1,
+        edge,
+    });
+    pose
+}
+
+#[test]
+fn test_validate_globalist() {
+    let problem = bonus_problem();
+    // 1 本目は 16 -> 20 (0.25 伸びる)、2 本目はそのまま。合計 0.25 <= 2 * 0.15
+    let pose = bonus_pose(
+        &[(1.0, 1.0), (5.0, 3.0), (5.0, 7.0)],
+        BonusType::Globalist,
+        None,
+    );
+    let report = validate(&problem, &pose);
+    assert!(report.is_valid());
+    assert_eq!(
+        report.absorbed,
+        vec![(BonusType::Globalist, Violation::StretchedEdge(0))]
+    );
+
+    // 16 -> 25 (0.5625) は合計でも超える
+    let pose = bonus_pose(
+        &[(1.0, 1.0), (5.0, 4.0), (5.0, 8.0)],
+        BonusType::Globalist,
+        None,
+    );
+    let report = validate(&problem, &pose);
+    assert!(!report.is_valid());
+    assert_eq!(report.stretched_edges, vec![0]);
+}
+
+#[test]
+fn test_validate_superflex() {
+    let problem = bonus_problem();
+    let pose = bonus_pose(
+        &[(1.0, 1.0), (6.0, 1.0), (6.0, 5.0)],
+        BonusType::Superflex,
+        None,
+    );
+    let report = validate(&problem, &pose);
+    assert!(report.is_valid());
+    assert_eq!(
+        report.absorbed,
+        vec![(BonusType::Superflex, Violation::StretchedEdge(0))]
+    );
+
+    // 2 本伸びていれば許されない
+    let pose = bonus_pose(
+        &[(1.0, 1.0), (6.0, 1.0), (6.0, 6.0)],
+        BonusType::Superflex,
+        None,
+    );
+    assert!(!validate(&problem, &pose).is_valid());
+}
+
+#[test]
+fn test_validate_wallhack() {
+    let problem = bonus_problem();
+    // 頂点 1 だけが外に出ている
+    let pose = bonus_pose(
+        &[(7.0, 1.0), (11.0, 1.0), (7.0, 1.0)],
+        BonusType::Wallhack,
+        None,
+    );
+    let report = validate(&problem, &pose);
+    assert!(report.is_valid());
+    assert!(report
+        .absorbed
+        .contains(&(BonusType::Wallhack, Violation::OutsideVertex(1))));
+    assert!(report
+        .absorbed
+        .contains(&(BonusType::Wallhack, Violation::OutsideEdge(0))));
+
+    // 2 頂点が外に出ていれば許されない
+    let pose = bonus_pose(
+        &[(11.0, 1.0), (15.0, 1.0), (15.0, 5.0)],
+        BonusType::Wallhack,
+        None,
+    );
+    assert!(!validate(&problem, &pose).is_valid());
+}
+
+#[test]
+fn test_validate_break_a_leg() {
+    let problem = bonus_problem();
+    // 辺 (0, 1) を折って、新しい頂点 3 を中点に置く
+    let pose = bonus_pose(
+        &[(1.0, 1.0), (5.0, 1.0), (5.0, 5.0), (3.0, 1.0)],
+        BonusType::BreakALeg,
+        Some((1, 0)),
+    );
+    assert!(validate(&problem, &pose).is_valid());
+
+    // 頂点が足りない
+    let pose = bonus_pose(
+        &[(1.0, 1.0), (5.0, 1.0), (5.0, 5.0)],
+        BonusType::BreakALeg,
+        Some((0, 1)),
+    );
+    assert!(!validate(&problem, &pose).is_valid());
+
+    // 存在しない辺
+    let pose = bonus_pose(
+        &[(1.0, 1.0), (5.0, 1.0), (5.0, 5.0), (3.0, 1.0)],
+        BonusType::BreakALeg,
+        Some((0, 2)),
+    );
+    assert!(!validate(&problem, &pose).bonus_errors.is_empty());
+}
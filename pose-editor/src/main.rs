@@ -0,0 +1,326 @@
+extern crate lib;
+
+use lib::data::{BonusType, Point, Pose, Problem, UsedBonus};
+use lib::paths::DataDir;
+use lib::store::{SolutionMeta, SolutionStore};
+use lib::validation::{dislike, posed_figure, validate};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+
+// ブラウザで pose を手で動かすためのローカルの HTTP サーバー
+//   GET  /                  編集画面
+//   GET  /api/problem/{id}  問題
+//   GET  /api/pose/{id}     best の解 (なければ 404)
+//   POST /api/check/{id}    送った pose の検証結果と dislike
+//   POST /api/save/{id}     valid なら solutions に残し、best より良ければ best に保存
+const PAGE: &str = include_str!("page.html");
+
+const DEFAULT_PORT: u16 = 8080;
+
+struct Request {
+    method: String,
+    path: String,
+    body: String,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: String) -> Response {
+        Response {
+            status: "200 OK",
+            content_type,
+            body,
+        }
+    }
+
+    fn json(body: String) -> Response {
+        Response::ok("application/json", body)
+    }
+
+    fn error(status: &'static str, message: &str) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            body: format!("{{\"error\": \"{}\"}}", message.replace('"', "'")),
+        }
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut tokens = line.split_whitespace();
+    let method = tokens.next().ok_or("empty request")?.to_string();
+    let path = tokens.next().ok_or("no path")?.to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).map_err(|e| e.to_string())?;
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse::<usize>().map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    Ok(Request {
+        method,
+        path,
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) {
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    // ブラウザが途中で切っても止まらないように、書き込みの失敗は無視する
+    let _ = stream.write_all(header.as_bytes());
+    let _ = stream.write_all(response.body.as_bytes());
+}
+
+// ブラウザから送られた pose。Pose::from_json と違い、壊れていても panic しない
+fn parse_pose(json: &str) -> Result<Pose, String> {
+    let v = serde_json::from_str::<Value>(json).map_err(|e| e.to_string())?;
+    let mut pose = Pose::new();
+    for point in v["vertices"].as_array().ok_or("no vertices")? {
+        match (point[0].as_f64(), point[1].as_f64()) {
+            (Some(x), Some(y)) => pose.push(Point::new(x.round(), y.round())),
+            _ => return Err(format!("invalid vertex {}", point)),
+        }
+    }
+    if let Some(bonuses) = v["bonuses"].as_array() {
+        for bonus in bonuses {
+            let kind = bonus["bonus"]
+                .as_str()
+                .and_then(BonusType::from_name)
+                .ok_or("invalid bonus")?;
+            let problem = bonus["problem"].as_u64().ok_or("invalid bonus")? as usize;
+            let edge = match bonus["edge"].as_array() {
+                Some(e) => match (e[0].as_u64(), e[1].as_u64()) {
+                    (Some(v1), Some(v2)) => Some((v1 as usize, v2 as usize)),
+                    _ => return Err("invalid bonus edge".to_string()),
+                },
+                None => None,
+            };
+            pose.bonuses.push(UsedBonus {
+                bonus: kind,
+                problem,
+                edge,
+            });
+        }
+    }
+    Ok(pose)
+}
+
+fn json_list<T: ToString>(items: &[T]) -> String {
+    format!(
+        "[{}]",
+        items
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+// 検証結果。辺の番号は BREAK_A_LEG で折った後の figure のものなので、その辺も返す
+fn check(problem: &Problem, pose: &Pose) -> String {
+    let report = validate(problem, pose);
+    let edges = posed_figure(problem, pose)
+        .edges
+        .iter()
+        .map(|&(v1, v2)| format!("[{}, {}]", v1, v2))
+        .collect::<Vec<String>>();
+    let bonus_errors = report
+        .bonus_errors
+        .iter()
+        .map(|e| format!("\"{}\"", e.replace('"', "'")))
+        .collect::<Vec<String>>();
+    format!(
+        "{{\"valid\": {}, \"dislike\": {}, \"vertex_count_matches\": {}, \"edges\": [{}], \"stretched_edges\": {}, \"outside_edges\": {}, \"outside_vertices\": {}, \"bonus_errors\": [{}]}}",
+        report.is_valid(),
+        dislike(problem, pose),
+        report.vertex_count_matches,
+        edges.join(", "),
+        json_list(&report.stretched_edges),
+        json_list(&report.outside_edges),
+        json_list(&report.outside_vertices),
+        bonus_errors.join(", ")
+    )
+}
+
+// valid なら solutions に残し、best がないか best より dislike が小さければ best にも保存する
+fn save(data: &DataDir, problem: &Problem, pose: &Pose, id: usize) -> Response {
+    if !validate(problem, pose).is_valid() {
+        return Response::json("{\"saved\": false, \"reason\": \"pose is invalid\"}".to_string());
+    }
+    let current = dislike(problem, pose);
+    let store = SolutionStore::new(data);
+    if let Err(msg) = store.add(id, pose, current, &SolutionMeta::new("editor", None, "")) {
+        return Response::error("500 Internal Server Error", msg.as_str());
+    }
+    let best_filepath = data.best(id);
+    let update = || {
+        if Path::new(best_filepath.as_str()).exists() {
+            let best = Pose::from_file(best_filepath.as_str());
+            if validate(problem, &best).is_valid() && dislike(problem, &best) <= current {
+                return Err(dislike(problem, &best));
+            }
+        }
+        pose.save_file(best_filepath.clone());
+        // 手で作った解なので、前の解の再現用の seed は消す
+        let meta_filepath = format!("{}.meta.json", best_filepath.trim_end_matches(".json"));
+        let _ = fs::remove_file(meta_filepath);
+        Ok(())
+    };
+    match store.with_lock(update) {
+        Ok(Ok(())) => {}
+        Ok(Err(best)) => {
+            return Response::json(format!(
+                "{{\"saved\": false, \"reason\": \"best has dislike {}\"}}",
+                best
+            ))
+        }
+        Err(msg) => return Response::error("500 Internal Server Error", msg.as_str()),
+    }
+    println!("update! problem {}: dislike {} (editor)", id, current);
+    Response::json(format!("{{\"saved\": true, \"dislike\": {}}}", current))
+}
+
+fn handle(data: &DataDir, request: &Request) -> Response {
+    if request.method == "GET" && (request.path == "/" || request.path == "/index.html") {
+        return Response::ok("text/html", PAGE.to_string());
+    }
+    let parts = request
+        .path
+        .trim_start_matches('/')
+        .split('/')
+        .collect::<Vec<&str>>();
+    let (kind, id) = match parts.as_slice() {
+        ["api", kind, id] => match id.parse::<usize>() {
+            Ok(id) => (*kind, id),
+            Err(_) => return Response::error("400 Bad Request", "invalid problem id"),
+        },
+        _ => return Response::error("404 Not Found", "not found"),
+    };
+    if !data.has_problem(id) {
+        return Response::error("404 Not Found", "no such problem");
+    }
+    let problem = Problem::from_file(data.problem(id).as_str());
+    match (request.method.as_str(), kind) {
+        ("GET", "problem") => Response::json(problem.to_json()),
+        ("GET", "pose") => {
+            if data.has_best(id) {
+                Response::json(Pose::from_file(data.best(id).as_str()).to_json())
+            } else {
+                Response::error("404 Not Found", "no best pose")
+            }
+        }
+        ("POST", "check") | ("POST", "save") => {
+            let pose = match parse_pose(request.body.as_str()) {
+                Ok(pose) => pose,
+                Err(msg) => return Response::error("400 Bad Request", msg.as_str()),
+            };
+            if kind == "check" {
+                Response::json(check(&problem, &pose))
+            } else {
+                save(data, &problem, &pose, id)
+            }
+        }
+        _ => Response::error("404 Not Found", "not found"),
+    }
+}
+
+// pose-editor [--port <port>] [--data <dir>]
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    let data = DataDir::from_args(&args);
+    let port = match args.iter().position(|a| a == "--port") {
+        Some(i) => args
+            .get(i + 1)
+            .and_then(|p| p.parse::<u16>().ok())
+            .expect("usage: pose-editor [--port <port>] [--data <dir>]"),
+        None => DEFAULT_PORT,
+    };
+    // 手元のブラウザからだけ使う
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+    println!("open http://127.0.0.1:{}/ (data: {})", port, data.root());
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        let response = match read_request(&mut stream) {
+            Ok(request) => handle(&data, &request),
+            Err(msg) => Response::error("400 Bad Request", msg.as_str()),
+        };
+        write_response(&mut stream, &response);
+    }
+}
+
+#[test]
+fn test_parse_pose() {
+    let pose =
+        parse_pose("{\"vertices\": [[1, 2], [3.2, 4]], \"bonuses\": [{\"bonus\": \"WALLHACK\", \"problem\": 5}]}")
+            .unwrap();
+    assert_eq!(pose.vertices.len(), 2);
+    assert_eq!(pose.vertices[1].x, 3.0);
+    assert_eq!(pose.bonuses[0].bonus, BonusType::Wallhack);
+    assert!(parse_pose("{\"vertices\": [[1]]}").is_err());
+    assert!(parse_pose("not json").is_err());
+}
+
+#[test]
+fn test_handle() {
+    let data = DataDir::workspace();
+    let request = |method: &str, path: &str, body: &str| Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        body: body.to_string(),
+    };
+    assert_eq!(
+        handle(&data, &request("GET", "/", "")).content_type,
+        "text/html"
+    );
+    assert_eq!(
+        handle(&data, &request("GET", "/api/problem/x", "")).status,
+        "400 Bad Request"
+    );
+    assert!(handle(&data, &request("GET", "/api/problem/11", ""))
+        .body
+        .starts_with("{\"hole\""));
+
+    // 問題 11 は元の figure のままでは hole からはみ出す
+    let check = handle(
+        &data,
+        &request(
+            "POST",
+            "/api/check/11",
+            "{\"vertices\": [[0, 0], [10, 0], [10, 10]]}",
+        ),
+    );
+    let v = serde_json::from_str::<Value>(check.body.as_str()).unwrap();
+    assert_eq!(v["valid"], false);
+    assert_eq!(v["edges"].as_array().unwrap().len(), 3);
+    assert!(!v["outside_vertices"].as_array().unwrap().is_empty());
+}
@@ -1,22 +1,34 @@
 extern crate lib;
 
-use lib::client::get_problem;
+use lib::client::{get_problem, ClientConfig};
+use lib::paths::{create_parent, DataDir};
 use rayon::prelude::*;
+use std::env;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 
 fn main() {
-    (1..133).collect::<Vec<usize>>().par_iter().for_each(|&id| {
-        let maybe_problem = get_problem(id);
-        if let Ok(problem) = maybe_problem {
-            let file = File::create(format!("data/in/{}.json", id)).unwrap();
-            let mut buf = BufWriter::new(file);
-            if let Err(_) = buf.write_all(problem.as_bytes()) {
-                panic!("fail to write file {}", id);
-            }
-            println!("finish {}", id);
-        } else {
-            panic!("problem cannot downloaded.");
+    let data = DataDir::from_args(&env::args().collect::<Vec<String>>());
+    let client = match ClientConfig::load(&data) {
+        Ok(client) => client,
+        Err(msg) => {
+            println!("{}", msg);
+            std::process::exit(1);
         }
-    });
+    };
+    (1..133)
+        .collect::<Vec<usize>>()
+        .par_iter()
+        .for_each(|&id| match get_problem(&client, id) {
+            Ok(problem) => {
+                create_parent(data.problem(id).as_str()).unwrap();
+                let file = File::create(data.problem(id)).unwrap();
+                let mut buf = BufWriter::new(file);
+                if buf.write_all(problem.as_bytes()).is_err() {
+                    panic!("fail to write file {}", id);
+                }
+                println!("finish {}", id);
+            }
+            Err(msg) => panic!("problem {} cannot be downloaded: {}", id, msg),
+        });
 }
@@ -0,0 +1,167 @@
+use crate::neighborhood::Neighborhood;
+use crate::{
+    anneal, dislike, initial_solutions, penalty, save_pose, save_to_best, Budget, Chain, Monitor,
+    Solution, SolverConfig, SolverProblem, EPS,
+};
+use lib::data::{BonusType, Point, Pose, Problem, UsedBonus};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+// BREAK_A_LEG で折る辺を選んで解く
+#[derive(Clone, Debug)]
+pub struct BreakALegConfig {
+    // 試す辺の数。長い辺から順に試す
+    pub candidates: usize,
+}
+
+impl Default for BreakALegConfig {
+    fn default() -> BreakALegConfig {
+        BreakALegConfig { candidates: 8 }
+    }
+}
+
+// 長い辺ほど折ったときに自由度が増えるので、長い順に並べる
+fn candidate_edges(problem: &Problem, count: usize) -> Vec<(usize, usize)> {
+    let mut edges = problem.figure.edges.clone();
+    edges.sort_by(|&(a1, a2), &(b1, b2)| {
+        let a = problem.figure.vertices[a1].distance2(&problem.figure.vertices[a2]);
+        let b = problem.figure.vertices[b1].distance2(&problem.figure.vertices[b2]);
+        b.partial_cmp(&a).unwrap()
+    });
+    edges.truncate(count.max(1));
+    edges
+}
+
+#[test]
+fn test_candidate_edges() {
+    let mut problem = Problem::new();
+    for &(x, y) in [(0.0, 0.0), (1.0, 0.0), (1.0, 5.0), (4.0, 5.0)].iter() {
+        problem.figure.push(Point::new(x, y));
+    }
+    problem.figure.connect(0, 1);
+    problem.figure.connect(1, 2);
+    problem.figure.connect(2, 3);
+    assert_eq!(candidate_edges(&problem, 2), vec![(1, 2), (2, 3)]);
+}
+
+// 折る前の解に、折った辺の中点を新しい頂点として足す
+fn split_solution(
+    base_problem: &SolverProblem,
+    base: &Solution,
+    problem: &SolverProblem,
+    edge: (usize, usize),
+) -> Solution {
+    let mut pose = base.to_pose(base_problem);
+    let middle = (pose.vertices[edge.0] + pose.vertices[edge.1]) / 2.0;
+    pose.push(Point::new(middle.x.round(), middle.y.round()));
+    let mut solution = Solution::from_pose(problem, &pose);
+    for p in solution.vertices.iter_mut() {
+        p.x = p.x.max(0).min(problem.width as i64 - 1);
+        p.y = p.y.max(0).min(problem.height as i64 - 1);
+    }
+    solution
+}
+
+// source で取った BREAK_A_LEG を使って解く
+// 予算の半分で候補の辺を比べ、残りの半分を一番良かった辺に使う
+pub fn solve_break_a_leg(
+    _problem: &Problem,
+    seed: u64,
+    budget: Budget,
+    problem_id: usize,
+    source: usize,
+    config: &SolverConfig,
+    break_a_leg: &BreakALegConfig,
+) -> Option<Pose> {
+    let epsilon = _problem.epsilon;
+    let base_problem = SolverProblem::new(_problem);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let base = initial_solutions(&base_problem, config, epsilon, problem_id, &mut rng)
+        .into_iter()
+        .next()
+        .unwrap();
+
+    let edges = candidate_edges(_problem, break_a_leg.candidates);
+    let trial_budget = budget.split(2 * edges.len());
+
+    let mut best: Option<(f64, SolverProblem, Solution)> = None;
+    for &edge in edges.iter() {
+        let mut split = _problem.clone();
+        split.figure.split_edge(edge.0, edge.1);
+        let mut problem = SolverProblem::new(&split);
+        problem.bonus_weight = config.bonus_weight;
+        problem.bonus = Some(UsedBonus {
+            bonus: BonusType::BreakALeg,
+            problem: source,
+            edge: Some(edge),
+        });
+        let neighborhood = Neighborhood::new(&problem, problem.ring_epsilon(epsilon));
+
+        let init = split_solution(&base_problem, &base, &problem, edge);
+        let chain = Chain {
+            init,
+            budget: trial_budget,
+            epsilon,
+        };
+        let (solution, eval, _) = anneal(
+            &problem,
+            &neighborhood,
+            config,
+            chain,
+            &mut rng,
+            &mut Monitor::default(),
+        );
+        println!("break ({}, {}): eval = {}", edge.0, edge.1, eval);
+        let improved = match &best {
+            Some((best_eval, _, _)) => eval < *best_eval,
+            None => true,
+        };
+        if improved {
+            best = Some((eval, problem, solution));
+        }
+    }
+
+    let (_, problem, solution) = best.unwrap();
+    let neighborhood = Neighborhood::new(&problem, problem.ring_epsilon(epsilon));
+    let chain = Chain {
+        init: solution,
+        budget: budget.split(2),
+        epsilon,
+    };
+    let (best_solution, _, _) = anneal(
+        &problem,
+        &neighborhood,
+        config,
+        chain,
+        &mut rng,
+        &mut Monitor::default(),
+    );
+
+    let edge = problem.bonus.unwrap().edge.unwrap();
+    println!("break ({}, {})", edge.0, edge.1);
+    println!("seed = {}", seed);
+    println!("score: {}", dislike(&problem, &best_solution));
+    let (p0, p1, p2) = penalty(&problem, &best_solution, epsilon);
+    println!("penalty: {} {} {}", p0, p1, p2);
+
+    let pose = best_solution.to_pose(&problem);
+    if p0 + p1 + p2 < EPS {
+        let saved = save_to_best(
+            config,
+            _problem,
+            &problem,
+            &best_solution,
+            problem_id,
+            seed,
+            "break_a_leg",
+        );
+        if saved {
+            Some(pose)
+        } else {
+            None
+        }
+    } else {
+        save_pose(&pose, config.out_filepath(problem_id), seed);
+        None
+    }
+}
@@ -0,0 +1,181 @@
+use lib::args::Args;
+use lib::data::{Point, Pose, Problem};
+use lib::paths::DataDir;
+use lib::store::{SolutionMeta, SolutionStore};
+use lib::validation::{collected_bonuses, dislike, is_bonus_collected, validate};
+use std::path::Path;
+
+// valid な解の頂点を bonus の位置に乗せて、bonus を取れるようにする
+#[derive(Clone, Debug, Default)]
+pub struct CollectConfig {
+    // 元の解から dislike がこれだけ悪化するまでは許す
+    pub tolerance: f64,
+}
+
+impl CollectConfig {
+    //   --tolerance <d>
+    pub fn from_args(args: &Args) -> Result<CollectConfig, String> {
+        Ok(CollectConfig {
+            tolerance: args.option_or("tolerance", 0.0)?,
+        })
+    }
+}
+
+// 1 頂点だけを動かすか、全体を平行移動して、頂点 v を position に乗せた候補
+fn candidates(pose: &Pose, v: usize, position: &Point) -> Vec<Pose> {
+    let mut moved = pose.clone();
+    moved.vertices[v] = *position;
+
+    let delta = *position - pose.vertices[v];
+    let mut translated = pose.clone();
+    for p in translated.vertices.iter_mut() {
+        *p = *p + delta;
+    }
+    vec![moved, translated]
+}
+
+// bonus を 1 つずつ、取れている bonus を失わずに取れるか試す
+pub fn collect(problem: &Problem, pose: &Pose, config: &CollectConfig) -> Pose {
+    let limit = dislike(problem, pose) + config.tolerance;
+    let mut current = pose.clone();
+
+    for bonus in problem.bonuses.iter() {
+        if is_bonus_collected(&current, bonus) {
+            continue;
+        }
+        let collected = collected_bonuses(problem, &current).len();
+
+        // 条件を満たす候補のうち、dislike が最も小さいもの
+        let mut best: Option<(f64, Pose)> = None;
+        for v in 0..current.vertices.len() {
+            for candidate in candidates(&current, v, &bonus.position) {
+                if collected_bonuses(problem, &candidate).len() <= collected {
+                    continue;
+                }
+                let d = dislike(problem, &candidate);
+                if d > limit || !validate(problem, &candidate).is_valid() {
+                    continue;
+                }
+                let improved = match &best {
+                    Some((best_dislike, _)) => d < *best_dislike,
+                    None => true,
+                };
+                if improved {
+                    best = Some((d, candidate));
+                }
+            }
+        }
+        if let Some((_, candidate)) = best {
+            current = candidate;
+        }
+    }
+    current
+}
+
+#[test]
+fn test_collect() {
+    use lib::data::{Bonus, BonusType};
+
+    // 10x10 の正方形の穴に、長さ 4 の辺が 1 本
+    let mut problem = Problem::new();
+    for &(x, y) in [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)].iter() {
+        problem.hole.push(Point::new(x, y));
+    }
+    problem.figure.push(Point::new(1.0, 1.0));
+    problem.figure.push(Point::new(5.0, 1.0));
+    problem.figure.connect(0, 1);
+    problem.epsilon = 0.0;
+    problem.bonuses.push(Bonus {
+        bonus: BonusType::Globalist,
+        problem: 2,
+        position: Point::new(3.0, 6.0),
+    });
+
+    let mut pose = Pose::new();
+    pose.push(Point::new(1.0, 1.0));
+    pose.push(Point::new(5.0, 1.0));
+
+    // 1 頂点だけ動かすと長さが変わるので、平行移動で乗せる
+    let config = CollectConfig { tolerance: 1000.0 };
+    let collected = collect(&problem, &pose, &config);
+    assert_eq!(collected_bonuses(&problem, &collected).len(), 1);
+    assert!(validate(&problem, &collected).is_valid());
+
+    // 許容範囲を超えて dislike を悪化させない
+    let config = CollectConfig { tolerance: 0.0 };
+    let collected = collect(&problem, &pose, &config);
+    assert!(dislike(&problem, &collected) <= dislike(&problem, &pose));
+}
+
+// best の解で bonus を取りに行き、取れた bonus を表示する
+// 新しく取れた bonus があれば上書きする
+pub fn collect_best(
+    data: &DataDir,
+    problem: &Problem,
+    problem_id: usize,
+    config: &CollectConfig,
+) -> Option<Pose> {
+    let best_filepath = data.best(problem_id);
+    if !Path::new(best_filepath.as_str()).exists() {
+        println!("no best pose for problem {}", problem_id);
+        return None;
+    }
+    let pose = Pose::from_file(best_filepath.as_str());
+    if !validate(problem, &pose).is_valid() {
+        println!("best pose of problem {} is invalid", problem_id);
+        return None;
+    }
+
+    let before = collected_bonuses(problem, &pose).len();
+    let collected = collect(problem, &pose, config);
+    let bonuses = collected_bonuses(problem, &collected);
+    for b in bonuses.iter() {
+        println!(
+            "problem {}: collect {} for problem {}",
+            problem_id,
+            b.bonus.as_str(),
+            b.problem
+        );
+    }
+    println!(
+        "problem {}: {} / {} bonuses, dislike {} -> {}",
+        problem_id,
+        bonuses.len(),
+        problem.bonuses.len(),
+        dislike(problem, &pose),
+        dislike(problem, &collected)
+    );
+
+    if bonuses.len() > before {
+        let meta = SolutionMeta::new(
+            "collect",
+            None,
+            format!("tolerance={}", config.tolerance).as_str(),
+        );
+        let store = SolutionStore::new(data);
+        if let Err(msg) = store.add(problem_id, &collected, dislike(problem, &collected), &meta) {
+            eprintln!("fail to add solution of problem {}: {}", problem_id, msg);
+        }
+        // 読んでから lock を取るまでに他のプロセスがより良い解を書いていたら、上書きしない
+        // 元の解のままなら、tolerance の分の悪化は承知の上なので書く
+        let update = || {
+            let current = Pose::from_file(best_filepath.as_str());
+            if current.to_json() != pose.to_json()
+                && validate(problem, &current).is_valid()
+                && dislike(problem, &current) < dislike(problem, &collected)
+            {
+                println!(
+                    "problem {}: best was updated meanwhile, keep it",
+                    problem_id
+                );
+                return;
+            }
+            // 元の解と同じ実行の結果なので、meta はそのまま残す
+            collected.save_file(best_filepath.clone());
+        };
+        if let Err(msg) = store.with_lock(update) {
+            eprintln!("fail to update best of problem {}: {}", problem_id, msg);
+        }
+    }
+    Some(collected)
+}
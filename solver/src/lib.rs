@@ -0,0 +1,1288 @@
+extern crate lib;
+
+mod break_a_leg;
+mod collect;
+mod neighborhood;
+mod placement;
+mod polish;
+mod relax;
+mod schedule;
+mod telemetry;
+mod tempering;
+mod trajectory;
+
+const EPS: f64 = 1e-8;
+const GLOBALIST_RING_RATE: f64 = 3.0;
+
+pub use break_a_leg::{solve_break_a_leg, BreakALegConfig};
+pub use collect::{collect_best, CollectConfig};
+use lib::algorithm::{next_permutation, HoleDistanceCalculator};
+use lib::args::Args;
+use lib::bonus::is_unlocked;
+use lib::data::{BonusType, Line, Point, Pose, Problem, UsedBonus};
+use lib::paths::DataDir;
+use lib::store::{SolutionMeta, SolutionStore};
+use lib::validation::{self, is_within_epsilon, validate};
+use neighborhood::Neighborhood;
+pub use neighborhood::NeighborhoodConfig;
+use placement::search_placements;
+pub use placement::PlacementConfig;
+pub use polish::{polish, PolishConfig};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use relax::relax;
+pub use relax::RelaxConfig;
+pub use schedule::{solve_scheduled, ScheduleConfig};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use telemetry::Telemetry;
+pub use telemetry::{TelemetryConfig, TelemetryFormat};
+pub use tempering::{solve_tempering, TemperingConfig};
+use trajectory::Recorder;
+pub use trajectory::TrajectoryConfig;
+
+fn is_acceptable(problem: &Problem, vertex_map: &Vec<usize>) -> bool {
+    // e というのは、figure の (v1, v2)
+    for e in problem.figure.edges.iter() {
+        let old_dist = {
+            let p0 = problem.figure.vertices[e.0];
+            let p1 = problem.figure.vertices[e.1];
+            p0.distance2(&p1)
+        };
+        let new_dist = {
+            let p0 = problem.hole.vertices[vertex_map[e.0]];
+            let p1 = problem.hole.vertices[vertex_map[e.1]];
+            p0.distance2(&p1)
+        };
+        if (new_dist / old_dist - 1.0).abs() > problem.epsilon {
+            return false;
+        }
+    }
+    true
+}
+
+pub fn solve(problem: &Problem) -> Option<Pose> {
+    let n = problem.hole.vertices.len();
+    if problem.figure.vertices.len() == n && n <= 12 {
+        println!("try solver 1");
+        // パターンを全部試して、対応する長さの辺が存在するならその組を出力
+        // fiture の座標の i 番目が、hole の vertex_map[i] 番目に相当する
+        let mut vertex_map = (0..n).collect::<Vec<usize>>();
+        loop {
+            if is_acceptable(problem, &vertex_map) {
+                let mut pose = Pose::new();
+                for i in 0..n {
+                    pose.push(problem.hole.vertices[vertex_map[i]]);
+                }
+                return Some(pose);
+            }
+            if !next_permutation(&mut vertex_map) {
+                break;
+            }
+        }
+        None
+    } else {
+        None
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Pos {
+    x: i64,
+    y: i64,
+}
+
+impl Pos {
+    fn new(x: i64, y: i64) -> Pos {
+        Pos { x: x, y: y }
+    }
+
+    fn distance(&self, p: &Pos) -> i64 {
+        let dy = self.y.max(p.y) - self.y.min(p.y);
+        let dx = self.x.max(p.x) - self.x.min(p.x);
+        dy * dy + dx * dx
+    }
+    fn to_point(&self) -> Point {
+        Point::new(self.x as f64, self.y as f64)
+    }
+}
+
+struct SolverProblem {
+    hole_distance: Vec<Vec<usize>>,
+    height: usize,
+    width: usize,
+
+    hole_vertices: Vec<Pos>,
+    offset_y: i64,
+    offset_x: i64,
+
+    orig_figure_vertices: Vec<Pos>,
+    // 丸める前の座標。BREAK_A_LEG で足した頂点は格子点に乗らないことがある
+    orig_figure_points: Vec<Point>,
+    figure_neighbors: Vec<Vec<usize>>,
+    figure_edges: Vec<(usize, usize)>,
+
+    // 使う bonus。penalty の計算が変わり、出力する pose で宣言する
+    bonus: Option<UsedBonus>,
+
+    // この問題で取れる bonus の位置
+    bonus_positions: Vec<Pos>,
+    // 0 より大きければ、bonus の位置に頂点を近づける項を評価値に足す
+    bonus_weight: f64,
+}
+
+fn average(vs: &Vec<Point>) -> Point {
+    let mut sum = Point::new(0.0, 0.0);
+    for i in vs.iter() {
+        sum.y += i.y;
+        sum.x += i.x;
+    }
+    sum.y /= vs.len() as f64;
+    sum.x /= vs.len() as f64;
+    sum
+}
+
+impl SolverProblem {
+    fn new(problem: &Problem) -> SolverProblem {
+        let mut ret = SolverProblem {
+            hole_distance: vec![],
+            height: 0,
+            width: 0,
+            hole_vertices: vec![],
+            offset_y: 0,
+            offset_x: 0,
+            orig_figure_vertices: vec![],
+            orig_figure_points: vec![],
+            figure_neighbors: problem.figure.neighbors.clone(),
+            figure_edges: problem.figure.edges.clone(),
+            bonus: None,
+            bonus_positions: vec![],
+            bonus_weight: 0.0,
+        };
+        // average shift
+        let hole_average = average(&problem.hole.vertices);
+        let figure_average = average(&problem.figure.vertices);
+        let shift = Point::new(
+            (hole_average.x - figure_average.x).round(),
+            (hole_average.y - figure_average.y).round(),
+        );
+
+        // 登場座標が (0, 0) で最小になるような調整
+        let mut min_x = std::i64::MAX;
+        let mut min_y = std::i64::MAX;
+
+        for p in problem.hole.vertices.iter() {
+            min_x = min_x.min(p.x as i64);
+            min_y = min_y.min(p.y as i64);
+        }
+        for p in problem.figure.vertices.iter() {
+            min_x = min_x.min((p.x + shift.x) as i64);
+            min_y = min_y.min((p.y + shift.y) as i64);
+        }
+
+        ret.offset_y = min_y;
+        ret.offset_x = min_x;
+
+        for p in problem.hole.vertices.iter() {
+            let x = p.x as i64 - min_x;
+            let y = p.y as i64 - min_y;
+
+            ret.height = ret.height.max((y + 1) as usize);
+            ret.width = ret.width.max((x + 1) as usize);
+
+            ret.hole_vertices.push(Pos::new(x, y));
+        }
+
+        for b in problem.bonuses.iter() {
+            ret.bonus_positions.push(Pos::new(
+                b.position.x as i64 - min_x,
+                b.position.y as i64 - min_y,
+            ));
+        }
+
+        for p in problem.figure.vertices.iter() {
+            let x = (p.x + shift.x) as i64 - min_x;
+            let y = (p.y + shift.y) as i64 - min_y;
+
+            ret.height = ret.height.max((y + 1) as usize);
+            ret.width = ret.width.max((x + 1) as usize);
+
+            ret.orig_figure_vertices.push(Pos::new(x, y));
+            ret.orig_figure_points.push(Point::new(
+                p.x + shift.x - min_x as f64,
+                p.y + shift.y - min_y as f64,
+            ));
+        }
+
+        let hdc = HoleDistanceCalculator::new(&problem.hole);
+        ret.hole_distance.resize(ret.height, vec![0; ret.width]);
+        for y in 0..ret.height {
+            for x in 0..ret.width {
+                let orig_x = (x as i64 + ret.offset_x) as f64;
+                let orig_y = (y as i64 + ret.offset_y) as f64;
+                let p = Point::new(orig_x, orig_y);
+                let d = hdc.distance(&p);
+                ret.hole_distance[y][x] = (d * d).round() as usize;
+            }
+        }
+        ret
+    }
+
+    // 元の figure での距離 (2 乗)。BREAK_A_LEG で折った辺では整数にならない
+    fn figure_distance(&self, i: usize, j: usize) -> f64 {
+        self.orig_figure_points[i].distance2(&self.orig_figure_points[j])
+    }
+
+    fn is_inside_grid(&self, p: &Pos) -> bool {
+        0 <= p.y && p.y < self.height as i64 && 0 <= p.x && p.x < self.width as i64
+    }
+
+    fn uses_bonus(&self, bonus: BonusType) -> bool {
+        self.bonus.is_some_and(|b| b.bonus == bonus)
+    }
+
+    // 近傍の ring で候補にする辺の伸び縮みの幅
+    // GLOBALIST では 1 本あたりの上限がないので、他の辺と融通できるように広めに取る
+    fn ring_epsilon(&self, epsilon: f64) -> f64 {
+        if self.uses_bonus(BonusType::Globalist) {
+            epsilon * GLOBALIST_RING_RATE
+        } else {
+            epsilon
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Solution {
+    vertices: Vec<Pos>,
+}
+
+impl Solution {
+    fn new(init: &Vec<Pos>) -> Solution {
+        Solution {
+            vertices: init.clone(),
+        }
+    }
+
+    fn to_pose(&self, problem: &SolverProblem) -> Pose {
+        let mut pose = Pose::new();
+        for p in self.vertices.iter() {
+            let x = (p.x + problem.offset_x) as f64;
+            let y = (p.y + problem.offset_y) as f64;
+            pose.push(Point::new(x, y));
+        }
+        if let Some(bonus) = problem.bonus {
+            pose.bonuses.push(bonus);
+        }
+        pose
+    }
+
+    fn from_pose(problem: &SolverProblem, pose: &Pose) -> Solution {
+        let mut solution = Solution { vertices: vec![] };
+        for v in pose.vertices.iter() {
+            solution.vertices.push(Pos::new(
+                v.x as i64 - problem.offset_x,
+                v.y as i64 - problem.offset_y,
+            ));
+        }
+        solution
+    }
+}
+
+fn dislike(problem: &SolverProblem, sol: &Solution) -> f64 {
+    let mut sum = 0;
+    for hv in problem.hole_vertices.iter() {
+        let mut dist = std::i64::MAX;
+        for pv in sol.vertices.iter() {
+            dist = dist.min(pv.distance(hv));
+        }
+        sum += dist;
+    }
+    sum as f64
+}
+
+// 各 bonus の位置から最も近い頂点までの距離 (2 乗) の和。dislike と同じ形
+fn bonus_distance(problem: &SolverProblem, sol: &Solution) -> f64 {
+    let mut sum = 0;
+    for bv in problem.bonus_positions.iter() {
+        let mut dist = i64::MAX;
+        for pv in sol.vertices.iter() {
+            dist = dist.min(pv.distance(bv));
+        }
+        sum += dist;
+    }
+    sum as f64
+}
+
+// 穴の内部からの距離
+fn hole_penalty(problem: &SolverProblem, sol: &Solution) -> f64 {
+    let mut p0 = 0.0;
+    for pos in sol.vertices.iter() {
+        p0 += problem.hole_distance[pos.y as usize][pos.x as usize] as f64;
+    }
+    p0
+}
+
+// GLOBALIST: 伸び縮みの合計が |E| * epsilon を超えた分
+// 辺ごとの penalty と同じくらいの大きさになるように、元の辺の長さの平均を掛ける
+fn globalist_penalty(problem: &SolverProblem, sol: &Solution, epsilon: f64) -> f64 {
+    let mut total = 0.0;
+    let mut orig_sum = 0.0;
+    for &(v1, v2) in problem.figure_edges.iter() {
+        let orig_dist = problem.figure_distance(v1, v2);
+        let cur_dist = sol.vertices[v1].distance(&sol.vertices[v2]);
+        total += (cur_dist as f64 / orig_dist - 1.0).abs();
+        orig_sum += orig_dist;
+    }
+    let edge_count = problem.figure_edges.len() as f64;
+    let budget = edge_count * epsilon;
+    if total > budget {
+        (total - budget) * orig_sum / edge_count
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+fn bonus_test_problem() -> Problem {
+    // 10x10 の正方形の穴に、長さ 4 の辺が 2 本
+    let mut problem = Problem::new();
+    for &(x, y) in [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)].iter() {
+        problem.hole.push(Point::new(x, y));
+    }
+    for &(x, y) in [(1.0, 1.0), (5.0, 1.0), (5.0, 5.0)].iter() {
+        problem.figure.push(Point::new(x, y));
+    }
+    problem.figure.connect(0, 1);
+    problem.figure.connect(1, 2);
+    problem
+}
+
+#[cfg(test)]
+fn test_bonus(bonus: BonusType) -> Option<UsedBonus> {
+    Some(UsedBonus {
+        bonus,
+        problem: 1,
+        edge: None,
+    })
+}
+
+#[test]
+fn test_globalist_penalty() {
+    let problem = bonus_test_problem();
+    let epsilon = 0.15;
+    let mut solver_problem = SolverProblem::new(&problem);
+
+    // 1 本目だけ 16 -> 20 に伸ばす。辺ごとには超えるが、合計 0.25 は 2 * 0.15 以内
+    let mut solution = Solution::new(&solver_problem.orig_figure_vertices);
+    solution.vertices[1].y += 2;
+    solution.vertices[2].y += 2;
+    assert!(penalty(&solver_problem, &solution, epsilon).1 > 0.0);
+
+    solver_problem.bonus = test_bonus(BonusType::Globalist);
+    assert_eq!(penalty(&solver_problem, &solution, epsilon).1, 0.0);
+    assert_eq!(solution.to_pose(&solver_problem).bonuses.len(), 1);
+}
+
+#[test]
+fn test_superflex_penalty() {
+    let problem = bonus_test_problem();
+    let epsilon = 0.15;
+    let mut solver_problem = SolverProblem::new(&problem);
+    solver_problem.bonus = test_bonus(BonusType::Superflex);
+
+    // 1 本目だけ 16 -> 25 に伸ばすなら許される
+    let mut solution = Solution::new(&vec![Pos::new(1, 4), Pos::new(6, 4), Pos::new(6, 8)]);
+    assert_eq!(penalty(&solver_problem, &solution, epsilon).1, 0.0);
+
+    // 2 本とも伸ばすと、伸びの小さい方が残る
+    solution.vertices[2].y += 1;
+    assert!(penalty(&solver_problem, &solution, epsilon).1 > 0.0);
+}
+
+#[test]
+fn test_wallhack_penalty() {
+    // L 字の穴で、頂点 2 が右上の欠けた部分に出ている
+    let mut problem = bonus_test_problem();
+    problem.hole.clear();
+    for &(x, y) in [
+        (0.0, 0.0),
+        (10.0, 0.0),
+        (10.0, 5.0),
+        (5.0, 5.0),
+        (5.0, 10.0),
+        (0.0, 10.0),
+    ]
+    .iter()
+    {
+        problem.hole.push(Point::new(x, y));
+    }
+    let epsilon = 0.15;
+    let mut solver_problem = SolverProblem::new(&problem);
+    let solution = Solution::new(&vec![Pos::new(2, 4), Pos::new(6, 4), Pos::new(6, 8)]);
+    let (p0, _, p2) = penalty(&solver_problem, &solution, epsilon);
+    assert!(p0 > 0.0 && p2 > 0.0);
+
+    solver_problem.bonus = test_bonus(BonusType::Wallhack);
+    let (p0, p1, p2) = penalty(&solver_problem, &solution, epsilon);
+    assert_eq!((p0, p1, p2), (0.0, 0.0, 0.0));
+
+    // 頂点はすべて穴の中で、辺 1-2 だけが欠けた部分を横切る。外に出ている頂点がないので許さない
+    let solution = Solution::new(&vec![Pos::new(2, 2), Pos::new(8, 4), Pos::new(4, 8)]);
+    let (p0, _, p2) = penalty(&solver_problem, &solution, epsilon);
+    assert_eq!(p0, 0.0);
+    assert!(p2 > 0.0);
+
+    // 頂点 1 を共有する 2 本の辺がどちらも欠けた部分を横切る。validate も同じく許さない
+    let solution = Solution::new(&vec![Pos::new(4, 9), Pos::new(9, 4), Pos::new(3, 8)]);
+    let (p0, _, p2) = penalty(&solver_problem, &solution, epsilon);
+    assert_eq!(p0, 0.0);
+    assert!(p2 > 0.0);
+    let report = validate(&problem, &solution.to_pose(&solver_problem));
+    assert_eq!(report.outside_edges, vec![0, 1]);
+    assert!(report.absorbed.is_empty());
+}
+
+fn penalty(problem: &SolverProblem, sol: &Solution, epsilon: f64) -> (f64, f64, f64) {
+    // 穴の内部からの距離
+    let mut p0 = hole_penalty(problem, sol);
+    // WALLHACK: 最もはみ出している頂点は外に出てよく、その頂点につながる辺もはみ出してよい
+    // どの頂点も穴の中にあれば、辺のはみ出しは許さない
+    let wallhack_vertex = if problem.uses_bonus(BonusType::Wallhack) {
+        let distance = |v: usize| {
+            let pos = sol.vertices[v];
+            problem.hole_distance[pos.y as usize][pos.x as usize]
+        };
+        let v = validation::wallhack_vertex(sol.vertices.len(), distance);
+        if let Some(v) = v {
+            p0 -= distance(v) as f64;
+        }
+        v
+    } else {
+        None
+    };
+    // 頂点間の距離
+    let mut p1 = 0.0;
+    if problem.uses_bonus(BonusType::Globalist) {
+        p1 = globalist_penalty(problem, sol, epsilon);
+    } else {
+        let mut worst = 0.0;
+        for i in 0..sol.vertices.len() {
+            for &ni in problem.figure_neighbors[i].iter() {
+                let orig_dist = problem.figure_distance(i, ni);
+                let cur_dist = sol.vertices[i].distance(&sol.vertices[ni]);
+                if !is_within_epsilon(epsilon, orig_dist, cur_dist as f64) {
+                    let rate = (cur_dist as f64 / orig_dist - 1.0).abs();
+                    p1 += rate * orig_dist;
+                    worst = f64::max(worst, rate * orig_dist);
+                }
+            }
+        }
+        // SUPERFLEX: 最も伸び縮みしている辺は許される (各辺は両端から 2 回数えている)
+        if problem.uses_bonus(BonusType::Superflex) {
+            p1 -= worst * 2.0;
+        }
+    }
+    // 構成する辺が、hole の辺と被ってはいけない
+    let mut p2 = 0.0;
+    let n = sol.vertices.len();
+    let m = problem.hole_vertices.len();
+
+    for i in 0..n {
+        for &j in problem.figure_neighbors[i].iter() {
+            if wallhack_vertex == Some(i) || wallhack_vertex == Some(j) {
+                continue;
+            }
+            let v1 = sol.vertices[i].to_point();
+            let v2 = sol.vertices[j].to_point();
+            let l1 = Line::new(v1, v2);
+
+            for hi in 0..m {
+                let nhi = (hi + 1) % m;
+                let v3 = problem.hole_vertices[hi].to_point();
+                let v4 = problem.hole_vertices[nhi].to_point();
+                let l2 = Line::new(v3, v4);
+
+                if l1.intersect(&l2) {
+                    p2 += 1.0;
+                }
+            }
+        }
+    }
+
+    (p0, p1, p2)
+}
+
+fn evaluate_all(problem: &SolverProblem, sol: &Solution, epsilon: f64) -> f64 {
+    evaluate_with_validity(problem, sol, epsilon).0
+}
+
+// 評価値と、ペナルティが 0 (= valid) かどうか
+fn evaluate_with_validity(problem: &SolverProblem, sol: &Solution, epsilon: f64) -> (f64, bool) {
+    let (p0, p1, p2) = penalty(problem, sol, epsilon);
+
+    let scale = 1e-4;
+    let score_penalty_rate = 100.0;
+    let p01_rate = 10.0;
+    let p02_rate = 100.0;
+
+    let mut score = dislike(problem, sol);
+    if problem.bonus_weight > 0.0 {
+        score += bonus_distance(problem, sol) * problem.bonus_weight;
+    }
+
+    let eval = (score + (p0 + p1 * p01_rate + p2 * p02_rate) * score_penalty_rate) * scale;
+    (eval, p0 + p1 + p2 < EPS)
+}
+
+// 既存の解から焼きなましを始める
+#[derive(Clone, Debug, Default)]
+pub struct WarmStart {
+    // None なら data の best/{id}.json
+    pub filepath: Option<String>,
+    // 各頂点を x, y それぞれ [-perturbation, perturbation] の範囲でランダムにずらす
+    pub perturbation: i64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SolverConfig {
+    pub neighborhood: NeighborhoodConfig,
+    // 指定があれば、既存の解を初期解にする
+    pub warm_start: Option<WarmStart>,
+    // 指定があれば、向きと位置を全探索して良いものをいくつか初期解にする
+    pub placement: Option<PlacementConfig>,
+    // 指定があれば、焼きなましの前に連続緩和で初期解を作る
+    pub relax: Option<RelaxConfig>,
+    // 指定があれば、その bonus を使う前提で解く
+    pub bonus: Option<UsedBonus>,
+    // bonus の位置に頂点を近づける項の重み (dislike に対する比)。0 なら考えない
+    pub bonus_weight: f64,
+    // 問題や解を読み書きする data ディレクトリ
+    pub data: DataDir,
+    // valid にならなかった解の出力先。None なら data の out
+    pub out_dir: Option<String>,
+    // 指定があれば、solve2 の途中経過をファイルに記録する
+    pub trajectory: Option<TrajectoryConfig>,
+    // 指定があれば、solve2 の評価値や受理率を一定の間隔で書き出す
+    pub telemetry: Option<TelemetryConfig>,
+}
+
+// SolverConfig::from_args が読む、値を取らないオプション
+pub const FLAGS: &[&str] = &["relax", "placement", "collect-bonuses"];
+
+impl SolverConfig {
+    // solver と cli の solve に共通のオプションから作る。既存の解から始めるのは --warm-start のときだけ
+    //   --trajectory <n>, --telemetry <ms>, --telemetry-format <jsonl|csv>, --label <name>, --out <dir>
+    //   --relax, --placement, --warm-start <file|best|none>, --perturbation <k>
+    //   --collect-bonuses, --bonus-weight <w>
+    pub fn from_args(args: &Args, data: &DataDir) -> Result<SolverConfig, String> {
+        let trajectory =
+            args.parsed_option::<usize>("trajectory")?
+                .map(|interval| TrajectoryConfig {
+                    interval,
+                    ..TrajectoryConfig::default()
+                });
+        let telemetry_format = args.option("telemetry-format").unwrap_or("jsonl");
+        let telemetry = match args.parsed_option::<u128>("telemetry")? {
+            Some(interval) => Some(TelemetryConfig {
+                interval,
+                format: TelemetryFormat::from_name(telemetry_format)?,
+                label: args.option("label").map(|label| label.to_string()),
+                ..TelemetryConfig::default()
+            }),
+            None => None,
+        };
+        // bonus の位置に頂点を寄せる。取るのは解いた後の collect_best
+        let bonus_weight = if args.flag("collect-bonuses") {
+            args.option_or("bonus-weight", 1.0)?
+        } else {
+            0.0
+        };
+        let perturbation = args.option_or("perturbation", 0)?;
+        let warm_start = match args.option("warm-start").unwrap_or("none") {
+            "none" => None,
+            "best" => Some(WarmStart {
+                filepath: None,
+                perturbation,
+            }),
+            filepath => Some(WarmStart {
+                filepath: Some(filepath.to_string()),
+                perturbation,
+            }),
+        };
+        // 初期解は既存の解か配置の探索のどちらか一方
+        if warm_start.is_some() && args.flag("placement") {
+            return Err("--placement cannot be used with --warm-start".to_string());
+        }
+        Ok(SolverConfig {
+            warm_start,
+            placement: if args.flag("placement") {
+                Some(PlacementConfig::default())
+            } else {
+                None
+            },
+            data: data.clone(),
+            relax: if args.flag("relax") {
+                Some(RelaxConfig::default())
+            } else {
+                None
+            },
+            bonus_weight,
+            out_dir: args.option("out").map(|dir| dir.to_string()),
+            trajectory,
+            telemetry,
+            ..SolverConfig::default()
+        })
+    }
+
+    // solutions に残す設定の要約
+    fn summary(&self) -> String {
+        match &self.bonus {
+            Some(bonus) => format!(
+                "{} bonus={}",
+                self.neighborhood.summary(),
+                bonus.bonus.as_str()
+            ),
+            None => self.neighborhood.summary(),
+        }
+    }
+
+    fn out_filepath(&self, problem_id: usize) -> String {
+        match &self.out_dir {
+            Some(dir) => format!("{}/{}.json", dir, problem_id),
+            None => self.data.out(problem_id),
+        }
+    }
+}
+
+// --bonus <type> を読む。BREAK_A_LEG は辺を選ぶ必要があるので solve_break_a_leg で使う
+pub fn bonus_from_args(args: &Args) -> Result<Option<BonusType>, String> {
+    match args.option("bonus") {
+        Some(name) => match BonusType::from_name(name) {
+            Some(BonusType::BreakALeg) => {
+                Err("use solver --mode break-a-leg for BREAK_A_LEG".to_string())
+            }
+            Some(bonus) => Ok(Some(bonus)),
+            None => Err(format!("unknown bonus {}", name)),
+        },
+        None => Ok(None),
+    }
+}
+
+// problem_id で bonus を使えるようにする問題を探す
+pub fn find_bonus_source(
+    data: &DataDir,
+    problem_id: usize,
+    bonus: BonusType,
+    max_id: usize,
+) -> Option<usize> {
+    (1..=max_id).find(|&id| {
+        data.has_problem(id)
+            && Problem::from_file(data.problem(id).as_str())
+                .bonuses
+                .iter()
+                .any(|b| b.bonus == bonus && b.problem == problem_id)
+    })
+}
+
+// source で取った bonus (GLOBALIST, SUPERFLEX, WALLHACK) を使って解き直す
+pub fn solve_with_bonus(
+    problem: &Problem,
+    seed: u64,
+    budget: Budget,
+    problem_id: usize,
+    source: usize,
+    config: &SolverConfig,
+    bonus: BonusType,
+) -> Option<Pose> {
+    let config = SolverConfig {
+        bonus: Some(UsedBonus {
+            bonus,
+            problem: source,
+            edge: None,
+        }),
+        ..config.clone()
+    };
+    solve2(problem, seed, budget, problem_id, &config)
+}
+
+fn warm_start_solution<R: Rng>(
+    problem: &SolverProblem,
+    warm_start: &WarmStart,
+    data: &DataDir,
+    problem_id: usize,
+    vertex_count: usize,
+    rng: &mut R,
+) -> Option<Solution> {
+    let filepath = match &warm_start.filepath {
+        Some(filepath) => filepath.clone(),
+        None => data.best(problem_id),
+    };
+    if !Path::new(filepath.as_str()).exists() {
+        println!("warm start: {} does not exist", filepath);
+        return None;
+    }
+    let pose = Pose::from_file(filepath.as_str());
+    if pose.vertices.len() != vertex_count {
+        println!("warm start: vertex count of {} does not match", filepath);
+        return None;
+    }
+    println!("warm start from {}", filepath);
+
+    let mut solution = Solution::from_pose(problem, &pose);
+    let k = warm_start.perturbation;
+    for p in solution.vertices.iter_mut() {
+        if k > 0 {
+            p.x += rng.gen_range(-k..=k);
+            p.y += rng.gen_range(-k..=k);
+        }
+        // 盤面の外には出さない
+        p.x = p.x.max(0).min(problem.width as i64 - 1);
+        p.y = p.y.max(0).min(problem.height as i64 - 1);
+    }
+    Some(solution)
+}
+
+#[test]
+fn test_warm_start_solution() {
+    let problem = bonus_test_problem();
+    let solver_problem = SolverProblem::new(&problem);
+    let root = std::env::temp_dir().join(format!("warm_start_{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    let filepath = root.join("pose.json").to_str().unwrap().to_string();
+    let mut pose = Pose::new();
+    pose.vertices = problem.figure.vertices.clone();
+    pose.save_file(filepath.clone());
+
+    let warm_start = WarmStart {
+        filepath: Some(filepath),
+        perturbation: 1000,
+    };
+    let data = DataDir::new(root.to_str().unwrap());
+    let n = problem.figure.vertices.len();
+    let mut rng = StdRng::seed_from_u64(1);
+    let solution =
+        warm_start_solution(&solver_problem, &warm_start, &data, 1, n, &mut rng).unwrap();
+    std::fs::remove_dir_all(root).unwrap();
+    assert_eq!(solution.vertices.len(), n);
+    for p in solution.vertices.iter() {
+        assert!(0 <= p.x && p.x < solver_problem.width as i64);
+        assert!(0 <= p.y && p.y < solver_problem.height as i64);
+    }
+    // 盤面より大きくずらしたので、どこかの頂点は端に寄る
+    assert!(solution
+        .vertices
+        .iter()
+        .any(|p| p.x == 0 || p.x == solver_problem.width as i64 - 1));
+}
+
+// 既存の解 -> 配置の探索 -> 元の figure の順で初期解を決める
+fn initial_solutions<R: Rng>(
+    problem: &SolverProblem,
+    config: &SolverConfig,
+    epsilon: f64,
+    problem_id: usize,
+    rng: &mut R,
+) -> Vec<Solution> {
+    let n = problem.orig_figure_vertices.len();
+    let warm_solution = config.warm_start.as_ref().and_then(|warm_start| {
+        warm_start_solution(problem, warm_start, &config.data, problem_id, n, rng)
+    });
+    let solutions = if let Some(solution) = warm_solution {
+        vec![solution]
+    } else if let Some(placement_config) = &config.placement {
+        search_placements(problem, placement_config)
+    } else {
+        vec![Solution::new(&problem.orig_figure_vertices)]
+    };
+
+    if let Some(relax_config) = &config.relax {
+        solutions
+            .iter()
+            .map(|solution| relax(problem, solution, epsilon, relax_config))
+            .collect()
+    } else {
+        solutions
+    }
+}
+
+// 打ち切り条件
+#[derive(Clone, Copy, Debug)]
+pub enum Budget {
+    // ミリ秒
+    Time(u128),
+    // 反復回数
+    // 温度も反復回数から決まるので、同じ seed なら同じ軌跡を再現できる
+    Iterations(usize),
+}
+
+impl Budget {
+    // 進捗率を [0, 1) で返す。使い切っていたら None
+    fn progress(&self, counter: usize, timer: &Instant) -> Option<f64> {
+        match *self {
+            Budget::Time(timeout) => {
+                let elapsed = timer.elapsed().as_millis();
+                if elapsed > timeout {
+                    None
+                } else {
+                    Some(elapsed as f64 / timeout as f64)
+                }
+            }
+            Budget::Iterations(max_counter) => {
+                if counter >= max_counter {
+                    None
+                } else {
+                    Some(counter as f64 / max_counter as f64)
+                }
+            }
+        }
+    }
+
+    // count 本に等分した予算
+    fn split(&self, count: usize) -> Budget {
+        let count = count.max(1);
+        match *self {
+            Budget::Time(timeout) => Budget::Time(timeout / count as u128),
+            Budget::Iterations(max_counter) => Budget::Iterations(max_counter / count),
+        }
+    }
+}
+
+// 指定がなければ時刻から seed を決める
+pub fn default_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64
+}
+
+// pose と一緒に、再現用の seed を {filepath の拡張子を除いたもの}.meta.json に保存
+pub fn save_pose(pose: &Pose, filepath: String, seed: u64) {
+    let meta_filepath = format!("{}.meta.json", filepath.trim_end_matches(".json"));
+    pose.save_file(filepath);
+    let mut writer = BufWriter::new(File::create(meta_filepath.as_str()).unwrap());
+    if let Err(_msg) = writer.write(format!("{{\"seed\": {}}}", seed).as_bytes()) {
+        panic!("fail to save meta data");
+    }
+}
+
+// valid な解を solutions に残す。残せなくても解くのは続ける
+fn add_to_store(data: &DataDir, problem_id: usize, pose: &Pose, dislike: f64, meta: &SolutionMeta) {
+    if let Err(msg) = SolutionStore::new(data).add(problem_id, pose, dislike, meta) {
+        eprintln!("fail to add solution of problem {}: {}", problem_id, msg);
+    }
+}
+
+// 焼きなまし以外 (全探索など) で得た解を、valid で best より良ければ保存する
+pub fn save_if_better(
+    data: &DataDir,
+    problem: &Problem,
+    pose: &Pose,
+    problem_id: usize,
+    seed: u64,
+    solver: &str,
+) -> bool {
+    if !validate(problem, pose).is_valid() {
+        return false;
+    }
+    let current = validation::dislike(problem, pose);
+    add_to_store(
+        data,
+        problem_id,
+        pose,
+        current,
+        &SolutionMeta::new(solver, Some(seed), ""),
+    );
+    let best_filepath = data.best(problem_id);
+    // 他のプロセスと best の読み比べが混ざらないように lock を取る
+    let update = || {
+        let improved = if Path::new(best_filepath.as_str()).exists() {
+            let best = Pose::from_file(best_filepath.as_str());
+            !validate(problem, &best).is_valid() || current < validation::dislike(problem, &best)
+        } else {
+            true
+        };
+        if improved {
+            println!(
+                "update! problem {}: dislike {} (seed = {})",
+                problem_id, current, seed
+            );
+            save_pose(pose, best_filepath.clone(), seed);
+        }
+        improved
+    };
+    // lock が取れなくても solutions には残っているので、解くのは続ける
+    SolutionStore::new(data)
+        .with_lock(update)
+        .unwrap_or_else(|msg| {
+            eprintln!("fail to update best of problem {}: {}", problem_id, msg);
+            false
+        })
+}
+
+// 公式ルールで valid なら solutions に残し、best より良ければ best を更新する
+// valid でなければ out に書いて false を返す
+fn save_to_best(
+    config: &SolverConfig,
+    _problem: &Problem,
+    problem: &SolverProblem,
+    solution: &Solution,
+    problem_id: usize,
+    seed: u64,
+    solver: &str,
+) -> bool {
+    let pose = solution.to_pose(problem);
+    if !validate(_problem, &pose).is_valid() {
+        println!("problem {}: pose is invalid, save to out", problem_id);
+        save_pose(&pose, config.out_filepath(problem_id), seed);
+        return false;
+    }
+    let new_eval = dislike(problem, solution);
+    add_to_store(
+        &config.data,
+        problem_id,
+        &pose,
+        new_eval,
+        &SolutionMeta::new(solver, Some(seed), config.summary().as_str()),
+    );
+    // bonus がまだ取れていなければ、提出されないように best には書かない
+    if let Some(used) = pose
+        .bonuses
+        .iter()
+        .find(|used| !is_unlocked(&config.data, problem_id, used))
+    {
+        println!(
+            "problem {}: {} from {} is not collected yet, save to out",
+            problem_id,
+            used.bonus.as_str(),
+            used.problem
+        );
+        save_pose(&pose, config.out_filepath(problem_id), seed);
+        return true;
+    }
+
+    let best_filepath = config.data.best(problem_id);
+    let update = || {
+        if !Path::new(best_filepath.as_str()).exists() {
+            println!("create new file problem {} (seed = {})", problem_id, seed);
+            save_pose(&pose, best_filepath.clone(), seed);
+            return;
+        }
+
+        let best_pose = Pose::from_file(best_filepath.as_str());
+        let best_solution = Solution::from_pose(problem, &best_pose);
+
+        let best_eval = dislike(problem, &best_solution);
+        if best_eval > new_eval {
+            println!(
+                "update! problem {}: {} -> {} (seed = {})",
+                problem_id, best_eval, new_eval, seed
+            );
+            save_pose(&pose, best_filepath.clone(), seed);
+        }
+    };
+    if let Err(msg) = SolutionStore::new(&config.data).with_lock(update) {
+        eprintln!("fail to update best of problem {}: {}", problem_id, msg);
+    }
+    true
+}
+
+#[test]
+fn test_save_to_best_invalid() {
+    let mut problem = bonus_test_problem();
+    problem.epsilon = 0.15;
+    let solver_problem = SolverProblem::new(&problem);
+    let root = std::env::temp_dir().join(format!("save_to_best_{}", std::process::id()));
+    let config = SolverConfig {
+        data: DataDir::new(root.to_str().unwrap()),
+        ..SolverConfig::default()
+    };
+
+    // 1 本目を 16 -> 20 に伸ばしたので valid でない
+    let mut solution = Solution::new(&solver_problem.orig_figure_vertices);
+    solution.vertices[1].y += 2;
+    solution.vertices[2].y += 2;
+    let saved = save_to_best(&config, &problem, &solver_problem, &solution, 1, 0, "test");
+    let records = SolutionStore::new(&config.data).records().len();
+    let has_out = Path::new(config.data.out(1).as_str()).exists();
+    let has_best = config.data.has_best(1);
+
+    let solution = Solution::new(&solver_problem.orig_figure_vertices);
+    let saved_valid = save_to_best(&config, &problem, &solver_problem, &solution, 1, 0, "test");
+    let has_best_valid = config.data.has_best(1);
+    std::fs::remove_dir_all(root).unwrap();
+    assert_eq!((saved, records, has_out, has_best), (false, 0, true, false));
+    assert!(saved_valid && has_best_valid);
+}
+
+// 焼きなましの温度の逆数。経過時間の割合 0 から 1 に対して 0.5 から 1 へ上げる
+fn inverse_temperature(elapsed_rate: f64) -> f64 {
+    0.5 + 0.5 * elapsed_rate
+}
+
+// 焼きなましの途中経過の記録 (trajectory と telemetry)。指定がなければ何もしない
+#[derive(Default)]
+struct Monitor {
+    recorder: Option<Recorder>,
+    telemetry: Option<Telemetry>,
+}
+
+impl Monitor {
+    fn new(
+        config: &SolverConfig,
+        problem: &SolverProblem,
+        problem_id: usize,
+        seed: u64,
+    ) -> Monitor {
+        Monitor {
+            recorder: Recorder::new(config, problem, problem_id, seed),
+            telemetry: Telemetry::new(config, problem_id, seed),
+        }
+    }
+
+    // 次の初期解からの焼きなましに移る
+    fn next_chain(&mut self) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.next_chain();
+        }
+        if let Some(telemetry) = self.telemetry.as_mut() {
+            telemetry.next_chain();
+        }
+    }
+
+    fn finish(self) {
+        if let Some(recorder) = self.recorder {
+            recorder.finish();
+        }
+        if let Some(telemetry) = self.telemetry {
+            telemetry.finish();
+        }
+    }
+}
+
+// 1 本の焼きなましの初期解、予算と許される辺の伸び縮み
+struct Chain {
+    init: Solution,
+    budget: Budget,
+    epsilon: f64,
+}
+
+// 1 本の焼きなまし。最良の解とその評価値、反復回数を返す
+fn anneal(
+    problem: &SolverProblem,
+    neighborhood: &Neighborhood,
+    config: &SolverConfig,
+    chain: Chain,
+    rng: &mut StdRng,
+    monitor: &mut Monitor,
+) -> (Solution, f64, usize) {
+    let Chain {
+        init,
+        budget,
+        epsilon,
+    } = chain;
+    let mut counter = 0;
+
+    let timer = Instant::now();
+    let mut elapsed_rate = 0.0;
+
+    let mut current_solution = init;
+    let mut current_eval = evaluate_all(problem, &current_solution, epsilon);
+
+    let mut best_solution = current_solution.clone();
+    let mut best_eval = f64::MAX;
+
+    let accept = |de: f64, elapsed_rate: f64, rng: &mut StdRng| -> bool {
+        if de < 0.0 {
+            true
+        } else {
+            let rate = rng.gen::<f64>();
+            rate < (-de * inverse_temperature(elapsed_rate) / 1.0).exp()
+        }
+    };
+
+    loop {
+        if let Some(recorder) = monitor.recorder.as_mut() {
+            if recorder.is_due(counter) {
+                let temperature = 1.0 / inverse_temperature(elapsed_rate);
+                recorder.record(
+                    problem,
+                    &current_solution,
+                    epsilon,
+                    counter,
+                    temperature,
+                    current_eval,
+                );
+            }
+        }
+
+        // 近傍を選んで適用
+        if let Some(undo) =
+            neighborhood.apply(&config.neighborhood, problem, &mut current_solution, rng)
+        {
+            // 移動してコストを計算
+            let after_eval = evaluate_all(problem, &current_solution, epsilon);
+            let de = after_eval - current_eval;
+
+            // コストが改善するなら移動
+            let accepted = accept(de, elapsed_rate, rng);
+            if accepted {
+                current_eval = after_eval;
+
+                if best_eval > current_eval {
+                    best_eval = current_eval;
+                    best_solution = current_solution.clone();
+                }
+            } else {
+                undo.rollback(&mut current_solution);
+            }
+            if let Some(telemetry) = monitor.telemetry.as_mut() {
+                telemetry.count(undo.kind(), accepted);
+            }
+        }
+
+        counter += 1;
+        if counter % 1024 == 1023 {
+            if let Some(progress) = budget.progress(counter, &timer) {
+                elapsed_rate = progress;
+            } else {
+                break;
+            }
+            if let Some(telemetry) = monitor.telemetry.as_mut() {
+                if telemetry.is_due() {
+                    telemetry.write(
+                        counter,
+                        1.0 / inverse_temperature(elapsed_rate),
+                        current_eval,
+                        best_eval.min(current_eval),
+                        penalty(problem, &current_solution, epsilon),
+                    );
+                }
+            }
+        }
+
+        if counter % 16384 == 0 {
+            // 書き戻し
+            current_solution = best_solution.clone();
+            current_eval = best_eval;
+        }
+    }
+
+    // 最後の状態も書いておく
+    if let Some(telemetry) = monitor.telemetry.as_mut() {
+        telemetry.write(
+            counter,
+            1.0 / inverse_temperature(elapsed_rate),
+            current_eval,
+            best_eval.min(current_eval),
+            penalty(problem, &current_solution, epsilon),
+        );
+    }
+
+    if best_eval == f64::MAX {
+        best_eval = current_eval;
+    }
+    (best_solution, best_eval, counter)
+}
+
+pub fn solve2(
+    _problem: &Problem,
+    seed: u64,
+    budget: Budget,
+    problem_id: usize,
+    config: &SolverConfig,
+) -> Option<Pose> {
+    let mut problem = SolverProblem::new(_problem);
+    problem.bonus = config.bonus;
+    problem.bonus_weight = config.bonus_weight;
+    let neighborhood = Neighborhood::new(&problem, problem.ring_epsilon(_problem.epsilon));
+
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    // 初期解が複数あれば、予算を等分してそれぞれから焼きなます
+    let inits = initial_solutions(&problem, config, _problem.epsilon, problem_id, &mut rng);
+    let chain_budget = budget.split(inits.len());
+    let mut monitor = Monitor::new(config, &problem, problem_id, seed);
+
+    let mut counter = 0;
+    let mut best: Option<(Solution, f64)> = None;
+    for init in inits.into_iter() {
+        let chain = Chain {
+            init,
+            budget: chain_budget,
+            epsilon: _problem.epsilon,
+        };
+        let (solution, eval, chain_counter) = anneal(
+            &problem,
+            &neighborhood,
+            config,
+            chain,
+            &mut rng,
+            &mut monitor,
+        );
+        monitor.next_chain();
+        counter += chain_counter;
+        let improved = match &best {
+            Some((_, best_eval)) => eval < *best_eval,
+            None => true,
+        };
+        if improved {
+            best = Some((solution, eval));
+        }
+    }
+    let (best_solution, _) = best.unwrap();
+    monitor.finish();
+
+    println!("counter = {}", counter);
+    println!("seed = {}", seed);
+    println!("score: {}", dislike(&problem, &best_solution));
+    let (p0, p1, p2) = penalty(&problem, &best_solution, _problem.epsilon);
+    println!("penalty: {} {} {}", p0, p1, p2);
+
+    let pose = best_solution.to_pose(&problem);
+    if p0 + p1 + p2 < EPS {
+        let saved = save_to_best(
+            config,
+            _problem,
+            &problem,
+            &best_solution,
+            problem_id,
+            seed,
+            "solve2",
+        );
+        if saved {
+            Some(pose)
+        } else {
+            None
+        }
+    } else {
+        save_pose(&pose, config.out_filepath(problem_id), seed);
+        None
+    }
+}
+
+#[test]
+fn test_solve2_reproducible() {
+    let problem = Problem::from_file(DataDir::workspace().problem(12).as_str());
+    let run = |name: &str| {
+        let root = std::env::temp_dir().join(format!(
+            "solve2_reproducible_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let config = SolverConfig {
+            data: DataDir::new(root.to_str().unwrap()),
+            ..SolverConfig::default()
+        };
+        let pose = solve2(&problem, 7, Budget::Iterations(50000), 12, &config);
+        // valid でなければ out に書かれる
+        let saved = std::fs::read_to_string(config.data.best(12))
+            .or_else(|_| std::fs::read_to_string(config.data.out(12)))
+            .unwrap();
+        std::fs::remove_dir_all(root).unwrap();
+        (pose.map(|pose| pose.to_json()), saved)
+    };
+    assert_eq!(run("a"), run("b"));
+}
@@ -1,451 +1,292 @@
 extern crate lib;
+extern crate solver;
 
-const EPS: f64 = 1e-8;
-
-use lib::algorithm::{next_permutation, HoleDistanceCalculator};
-use lib::data::{Line, Point, Pose, Problem};
-use rand::prelude::ThreadRng;
-use rand::Rng;
+use lib::args::Args;
+use lib::data::{BonusType, Problem};
+use lib::paths::DataDir;
 use rayon::prelude::*;
-use std::path::Path;
-use std::time::Instant;
-
-fn is_acceptable(problem: &Problem, vertex_map: &Vec<usize>) -> bool {
-    // e というのは、figure の (v1, v2)
-    for e in problem.figure.edges.iter() {
-        let old_dist = {
-            let p0 = problem.figure.vertices[e.0];
-            let p1 = problem.figure.vertices[e.1];
-            p0.distance2(&p1)
-        };
-        let new_dist = {
-            let p0 = problem.hole.vertices[vertex_map[e.0]];
-            let p1 = problem.hole.vertices[vertex_map[e.1]];
-            p0.distance2(&p1)
-        };
-        if (new_dist / old_dist - 1.0).abs() > problem.epsilon {
-            return false;
-        }
-    }
-    true
+use solver::{
+    bonus_from_args, collect_best, default_seed, find_bonus_source, polish, save_if_better, solve,
+    solve2, solve_break_a_leg, solve_scheduled, solve_tempering, solve_with_bonus, BreakALegConfig,
+    Budget, CollectConfig, PolishConfig, ScheduleConfig, SolverConfig, TemperingConfig, FLAGS,
+};
+use std::env;
+use std::fs;
+use std::process;
+
+const MAX_ID: usize = 132;
+
+const USAGE: &str = "usage: solver [ids...] [options]
+
+ids are numbers or ranges like 1-10, separated by spaces or commas (default: 1-132)
+
+options:
+  --mode <name>      what to run (default solve)
+                       solve        exhaustive search and annealing
+                       tempering    replica exchange, one problem at a time on all cores
+                       polish       improve the valid best poses keeping them valid
+                       bonus        solve again using the bonus given by --bonus, unlocked in another problem
+                       collect      move vertices of the best poses onto bonus positions
+                       break-a-leg  solve again with BREAK_A_LEG unlocked in another problem
+  --timeout <ms>     time for each problem, or the length of one slice with --total
+                     (default 10000 for polish and 60000 for the others)
+  --total <ms>       share the total time by expected score gain (solve only; default when --timeout
+                     is not given, 1800000)
+  --threads <n>      number of threads (default: all cores)
+  --strategy <name>  exhaustive, annealing or both (solve only, default both)
+  --replicas <n>     number of replicas for --mode tempering (default: number of threads, at least 2)
+  --bonus <type>     GLOBALIST, SUPERFLEX or WALLHACK for --mode bonus
+  --collect-bonuses  pull vertices toward bonus positions while annealing, and collect bonuses after
+                     solving as --mode collect does
+  --bonus-weight <w> weight of the distance to bonus positions with --collect-bonuses (default 1)
+  --tolerance <d>    how much worse the dislike may get to collect a bonus with --mode collect or
+                     --collect-bonuses (default 0)
+  --seed <seed>      seed of the annealing (default: current time)
+  --out <dir>        directory for poses that are not valid (default <data>/out)
+  --warm-start <file|best|none>
+                     pose to start the annealing from (best: <data>/best/{id}.json, default none)
+  --perturbation <k> move each vertex of the warm start by up to k in x and y (default 0)
+  --placement        search orientations and positions of the figure for initial poses (not with
+                     --warm-start)
+  --relax            build initial poses by continuous relaxation before annealing
+  --trajectory <n>   record the annealing every n iterations to <data>/debug/trajectory_{id}.txt
+  --telemetry <ms>   append evaluation, penalties, temperature and acceptance rates every ms
+                     milliseconds to <data>/debug/telemetry_{id}.jsonl
+  --telemetry-format <jsonl|csv>, --label <name>
+                     format of the telemetry, and the config name in its rows (default: move weights)
+  --data <dir>       data directory (default $ICFPC_DATA_DIR or ./data)";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Solve,
+    Tempering,
+    Polish,
+    Bonus,
+    Collect,
+    BreakALeg,
 }
 
-fn solve(problem: &Problem) -> Option<Pose> {
-    let n = problem.hole.vertices.len();
-    if problem.figure.vertices.len() == n && n <= 12 {
-        println!("try solver 1");
-        // パターンを全部試して、対応する長さの辺が存在するならその組を出力
-        // fiture の座標の i 番目が、hole の vertex_map[i] 番目に相当する
-        let mut vertex_map = (0..n).collect::<Vec<usize>>();
-        loop {
-            if is_acceptable(problem, &vertex_map) {
-                let mut pose = Pose::new();
-                for i in 0..n {
-                    pose.push(problem.hole.vertices[vertex_map[i]]);
-                }
-                return Some(pose);
-            }
-            if !next_permutation(&mut vertex_map) {
-                break;
-            }
+impl Mode {
+    fn from_name(name: &str) -> Result<Mode, String> {
+        match name {
+            "solve" => Ok(Mode::Solve),
+            "tempering" => Ok(Mode::Tempering),
+            "polish" => Ok(Mode::Polish),
+            "bonus" => Ok(Mode::Bonus),
+            "collect" => Ok(Mode::Collect),
+            "break-a-leg" => Ok(Mode::BreakALeg),
+            _ => Err(format!("unknown mode {}", name)),
         }
-        None
-    } else {
-        None
-    }
-}
-
-#[derive(Clone, Copy)]
-struct Pos {
-    x: i64,
-    y: i64,
-}
-
-impl Pos {
-    fn new(x: i64, y: i64) -> Pos {
-        Pos { x: x, y: y }
-    }
-
-    fn distance(&self, p: &Pos) -> i64 {
-        let dy = self.y.max(p.y) - self.y.min(p.y);
-        let dx = self.x.max(p.x) - self.x.min(p.x);
-        dy * dy + dx * dx
-    }
-    fn to_point(&self) -> Point {
-        Point::new(self.x as f64, self.y as f64)
     }
 }
 
-struct SolverProblem {
-    hole_distance: Vec<Vec<usize>>,
-    height: usize,
-    width: usize,
-
-    hole_vertices: Vec<Pos>,
-    offset_y: i64,
-    offset_x: i64,
-
-    orig_figure_vertices: Vec<Pos>,
-    figure_neighbors: Vec<Vec<usize>>,
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Strategy {
+    // hole の頂点に figure の頂点を対応させる全探索
+    Exhaustive,
+    Annealing,
+    // 全探索で解けなかった問題を焼きなます
+    Both,
 }
 
-fn average(vs: &Vec<Point>) -> Point {
-    let mut sum = Point::new(0.0, 0.0);
-    for i in vs.iter() {
-        sum.y += i.y;
-        sum.x += i.x;
-    }
-    sum.y /= vs.len() as f64;
-    sum.x /= vs.len() as f64;
-    sum
-}
-
-impl SolverProblem {
-    fn new(problem: &Problem) -> SolverProblem {
-        let mut ret = SolverProblem {
-            hole_distance: vec![],
-            height: 0,
-            width: 0,
-            hole_vertices: vec![],
-            offset_y: 0,
-            offset_x: 0,
-            orig_figure_vertices: vec![],
-            figure_neighbors: problem.figure.neighbors.clone(),
-        };
-        // average shift
-        let hole_average = average(&problem.hole.vertices);
-        let figure_average = average(&problem.figure.vertices);
-        let shift = Point::new(
-            (hole_average.x - figure_average.x).round(),
-            (hole_average.y - figure_average.y).round(),
-        );
-
-        // 登場座標が (0, 0) で最小になるような調整
-        let mut min_x = std::i64::MAX;
-        let mut min_y = std::i64::MAX;
-
-        for p in problem.hole.vertices.iter() {
-            min_x = min_x.min(p.x as i64);
-            min_y = min_y.min(p.y as i64);
-        }
-        for p in problem.figure.vertices.iter() {
-            min_x = min_x.min((p.x + shift.x) as i64);
-            min_y = min_y.min((p.y + shift.y) as i64);
-        }
-
-        ret.offset_y = min_y;
-        ret.offset_x = min_x;
-
-        for p in problem.hole.vertices.iter() {
-            let x = p.x as i64 - min_x;
-            let y = p.y as i64 - min_y;
-
-            ret.height = ret.height.max((y + 1) as usize);
-            ret.width = ret.width.max((x + 1) as usize);
-
-            ret.hole_vertices.push(Pos::new(x, y));
+impl Strategy {
+    fn from_name(name: &str) -> Result<Strategy, String> {
+        match name {
+            "exhaustive" => Ok(Strategy::Exhaustive),
+            "annealing" => Ok(Strategy::Annealing),
+            "both" => Ok(Strategy::Both),
+            _ => Err(format!("unknown strategy {}", name)),
         }
-
-        for p in problem.figure.vertices.iter() {
-            let x = (p.x + shift.x) as i64 - min_x;
-            let y = (p.y + shift.y) as i64 - min_y;
-
-            ret.height = ret.height.max((y + 1) as usize);
-            ret.width = ret.width.max((x + 1) as usize);
-
-            ret.orig_figure_vertices.push(Pos::new(x, y));
-        }
-
-        let hdc = HoleDistanceCalculator::new(&problem.hole);
-        ret.hole_distance.resize(ret.height, vec![0; ret.width]);
-        for y in 0..ret.height {
-            for x in 0..ret.width {
-                let orig_x = (x as i64 + ret.offset_x) as f64;
-                let orig_y = (y as i64 + ret.offset_y) as f64;
-                let p = Point::new(orig_x, orig_y);
-                let d = hdc.distance(&p);
-                ret.hole_distance[y][x] = (d * d).round() as usize;
-            }
-        }
-        ret
-    }
-
-    fn figure_distance(&self, i: usize, j: usize) -> i64 {
-        self.orig_figure_vertices[i].distance(&self.orig_figure_vertices[j])
     }
 }
 
-#[derive(Clone)]
-struct Solution {
-    vertices: Vec<Pos>,
+fn load_problem(data: &DataDir, id: usize) -> Problem {
+    Problem::from_file(data.problem(id).as_str())
 }
 
-impl Solution {
-    fn new(init: &Vec<Pos>) -> Solution {
-        Solution {
-            vertices: init.clone(),
-        }
+fn solve_problems(
+    args: &Args,
+    data: &DataDir,
+    config: &SolverConfig,
+    seed: u64,
+) -> Result<(), String> {
+    let strategy = Strategy::from_name(args.option("strategy").unwrap_or("both"))?;
+    let timeout = args.parsed_option::<u128>("timeout")?;
+    let total = args.parsed_option::<u128>("total")?;
+
+    let mut ids = args.ids.clone();
+    if strategy != Strategy::Annealing {
+        let solved = ids
+            .par_iter()
+            .filter(|&&id| {
+                let problem = load_problem(data, id);
+                match solve(&problem) {
+                    Some(pose) => {
+                        save_if_better(data, &problem, &pose, id, seed, "exhaustive");
+                        true
+                    }
+                    None => false,
+                }
+            })
+            .copied()
+            .collect::<Vec<usize>>();
+        ids.retain(|id| !solved.contains(id));
     }
-
-    fn to_pose(&self, problem: &SolverProblem) -> Pose {
-        let mut pose = Pose::new();
-        for p in self.vertices.iter() {
-            let x = (p.x + problem.offset_x) as f64;
-            let y = (p.y + problem.offset_y) as f64;
-            pose.push(Point::new(x, y));
-        }
-        pose
+    if strategy == Strategy::Exhaustive || ids.is_empty() {
+        return Ok(());
     }
-}
 
-fn dislike(problem: &SolverProblem, sol: &Solution) -> f64 {
-    let mut sum = 0;
-    for hv in problem.hole_vertices.iter() {
-        let mut dist = std::i64::MAX;
-        for pv in sol.vertices.iter() {
-            dist = dist.min(pv.distance(hv));
+    match (total, timeout) {
+        (None, Some(timeout)) => {
+            // 全問題に同じ時間を使う
+            ids.par_iter().for_each(|&id| {
+                let problem = load_problem(data, id);
+                println!("load problem {} (seed = {}):", id, seed);
+                solve2(&problem, seed, Budget::Time(timeout), id, config);
+            });
         }
-        sum += dist;
-    }
-    sum as f64
-}
-
-fn penalty(problem: &SolverProblem, sol: &Solution, epsilon: f64) -> (f64, f64, f64) {
-    // 穴の内部からの距離
-    let mut p0 = 0.0;
-    for pos in sol.vertices.iter() {
-        p0 += problem.hole_distance[pos.y as usize][pos.x as usize] as f64;
-    }
-    // 頂点間の距離
-    let mut p1 = 0.0;
-    for i in 0..sol.vertices.len() {
-        for &ni in problem.figure_neighbors[i].iter() {
-            let orig_dist = problem.figure_distance(i, ni);
-            let cur_dist = sol.vertices[i].distance(&sol.vertices[ni]);
-            let rate = (cur_dist as f64 / orig_dist as f64 - 1.0).abs();
-            if rate > epsilon {
-                p1 += rate * orig_dist as f64;
+        (total, timeout) => {
+            // 得点が伸びそうな問題に時間を多く配る。--timeout は 1 回分の時間
+            let mut schedule = ScheduleConfig::default();
+            if let Some(total) = total {
+                schedule.total = total;
             }
-        }
-    }
-    // 構成する辺が、hole の辺と被ってはいけない
-    let mut p2 = 0.0;
-    let n = sol.vertices.len();
-    let m = problem.hole_vertices.len();
-
-    for i in 0..n {
-        for &j in problem.figure_neighbors[i].iter() {
-            let v1 = sol.vertices[i].to_point();
-            let v2 = sol.vertices[j].to_point();
-            let l1 = Line::new(v1, v2);
-
-            for hi in 0..m {
-                let nhi = (hi + 1) % m;
-                let v3 = problem.hole_vertices[hi].to_point();
-                let v4 = problem.hole_vertices[nhi].to_point();
-                let l2 = Line::new(v3, v4);
-
-                if l1.intersect(&l2) {
-                    p2 += 1.0;
-                }
+            if let Some(timeout) = timeout {
+                schedule.slice = timeout;
             }
+            solve_scheduled(&ids, seed, config, &schedule);
         }
     }
-
-    (p0, p1, p2)
+    Ok(())
 }
 
-fn evaluate_all(problem: &SolverProblem, sol: &Solution, epsilon: f64) -> f64 {
-    let (p0, p1, p2) = penalty(problem, sol, epsilon);
-
-    let scale = 1e-4;
-    let score_penalty_rate = 100.0;
-    let p01_rate = 10.0;
-    let p02_rate = 100.0;
-
-    (dislike(problem, sol) + (p0 + p1 * p01_rate + p2 * p02_rate) * score_penalty_rate) * scale
-}
-
-fn save_to_best(problem: &SolverProblem, solution: &Solution, problem_id: usize) {
-    let best_filepath = format!("data/best/{}.json", problem_id);
-
-    if !Path::new(best_filepath.as_str()).exists() {
-        println!("create new file problem {}", problem_id);
-        solution
-            .to_pose(problem)
-            .save_file(best_filepath.to_string());
-        return;
-    }
-
-    let best_pose = Pose::from_file(best_filepath.as_str());
-    let mut best_solution = Solution { vertices: vec![] };
-    for v in best_pose.vertices.iter() {
-        best_solution.vertices.push(Pos::new(
-            v.x as i64 - problem.offset_x,
-            v.y as i64 - problem.offset_y,
-        ));
+fn run(args: &Args, data: &DataDir) -> Result<(), String> {
+    if let Some(threads) = args.parsed_option::<usize>("threads")? {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(|e| e.to_string())?;
     }
-
-    let best_eval = dislike(problem, &best_solution);
-    let new_eval = dislike(problem, &solution);
-    if best_eval > new_eval {
-        println!(
-            "update! problem {}: {} -> {}",
-            problem_id, best_eval, new_eval
-        );
-        solution.to_pose(problem).save_file(best_filepath);
+    let mode = Mode::from_name(args.option("mode").unwrap_or("solve"))?;
+    let bonus = bonus_from_args(args)?;
+    if mode == Mode::Bonus && bonus.is_none() {
+        return Err("--mode bonus needs --bonus <type>".to_string());
     }
-}
-
-fn solve2(_problem: &Problem, _seed: u64, timeout: u128, problem_id: usize) -> Option<Pose> {
-    let problem = SolverProblem::new(_problem);
-
-    let n = problem.orig_figure_vertices.len();
-    let mut rng = rand::thread_rng();
-
-    let mut counter = 0;
-
-    let timer = Instant::now();
-    let mut elapsed_rate = 0.0;
-
-    let mut current_solution = Solution::new(&problem.orig_figure_vertices);
-    let mut current_eval = evaluate_all(&problem, &current_solution, _problem.epsilon);
-
-    let dy = [-1, 0, 1, 0];
-    let dx = [0, 1, 0, -1];
-
-    let mut best_solution = current_solution.clone();
-    let mut best_eval = std::f64::MAX;
-
-    let accept = |de: f64, elapsed_rate: f64, rng: &mut ThreadRng| -> bool {
-        if de < 0.0 {
-            true
-        } else {
-            let rate = rng.gen::<f64>();
-            rate < (-de * (0.5 + 0.5 * elapsed_rate) / 1.0).exp()
-        }
+    let seed = args.option_or("seed", default_seed())?;
+    let config = SolverConfig::from_args(args, data)?;
+    let out_dir = match &config.out_dir {
+        Some(dir) => dir.clone(),
+        None => data.out_dir(),
     };
-
-    loop {
-        let method = rng.gen::<usize>() % 1000;
-
-        if method <= 950 {
-            // 1頂点の場所移動
-            // 90%
-
-            // 頂点を選択
-            let v = rng.gen::<usize>() % n;
-
-            // 移動方向を選択
-            let dir = rng.gen::<usize>() % 4;
-
-            let ny = current_solution.vertices[v].y + dy[dir];
-            let nx = current_solution.vertices[v].x + dx[dir];
-
-            if !(0 <= ny && ny < problem.height as i64 && 0 <= nx && nx < problem.width as i64) {
-                continue;
-            }
-            current_solution.vertices[v].y = ny;
-            current_solution.vertices[v].x = nx;
-
-            // 移動してコストを計算
-            let after_eval = evaluate_all(&problem, &current_solution, _problem.epsilon);
-            let de = after_eval - current_eval;
-
-            // コストが改善するなら移動
-            if accept(de, elapsed_rate, &mut rng) {
-                current_eval = after_eval;
-
-                if best_eval > current_eval {
-                    best_eval = current_eval;
-                    best_solution = current_solution.clone();
-                }
-            } else {
-                current_solution.vertices[v].y -= dy[dir];
-                current_solution.vertices[v].x -= dx[dir];
-            }
-        } else {
-            // 隣接頂点の swap
-
-            // 頂点を選択
-            let v = rng.gen::<usize>() % n;
-            let nv = rng.gen::<usize>() % problem.figure_neighbors[v].len();
-
-            // 2座標の swap
-            current_solution.vertices.swap(v, nv);
-
-            // 移動してコストを計算
-            let after_eval = evaluate_all(&problem, &current_solution, _problem.epsilon);
-            let de = after_eval - current_eval;
-
-            // コストが改善するなら移動
-            if accept(de, elapsed_rate, &mut rng) {
-                current_eval = after_eval;
-
-                if best_eval > current_eval {
-                    best_eval = current_eval;
-                    best_solution = current_solution.clone();
-                }
-            } else {
-                current_solution.vertices.swap(v, nv);
+    fs::create_dir_all(out_dir.as_str()).map_err(|e| e.to_string())?;
+    let default_timeout = if mode == Mode::Polish { 10000 } else { 60000 };
+    let budget = Budget::Time(args.option_or("timeout", default_timeout)?);
+    println!("seed = {}", seed);
+
+    match mode {
+        Mode::Solve => solve_problems(args, data, &config, seed)?,
+        Mode::Tempering => {
+            // 1 問に全コアを使うので、1 問ずつ解く
+            let default_config = TemperingConfig::default();
+            let tempering_config = TemperingConfig {
+                replica_count: args.option_or("replicas", default_config.replica_count)?,
+                ..default_config
+            };
+            for &id in args.ids.iter() {
+                let problem = load_problem(data, id);
+                println!("load problem {} (seed = {}):", id, seed);
+                solve_tempering(&problem, seed, budget, id, &config, &tempering_config);
             }
         }
-
-        counter += 1;
-        if counter % 1024 == 1023 {
-            let elapsed = timer.elapsed().as_millis();
-            if elapsed > timeout {
-                break;
-            }
-            elapsed_rate = elapsed as f64 / timeout as f64;
+        Mode::Polish => {
+            // best の valid な解を、valid なまま改善する
+            let polish_config = PolishConfig::default();
+            args.ids.par_iter().for_each(|&id| {
+                let problem = load_problem(data, id);
+                println!("load problem {} (seed = {}):", id, seed);
+                polish(&problem, seed, budget, id, &config, &polish_config);
+            });
         }
-
-        if counter % 16384 == 0 {
-            // 書き戻し
-            current_solution = best_solution.clone();
-            current_eval = best_eval;
+        Mode::Bonus => {
+            let bonus = bonus.unwrap();
+            args.ids.par_iter().for_each(|&id| {
+                let source = match find_bonus_source(data, id, bonus, MAX_ID) {
+                    Some(source) => source,
+                    None => {
+                        println!("problem {}: no problem gives {}", id, bonus.as_str());
+                        return;
+                    }
+                };
+                let problem = load_problem(data, id);
+                println!(
+                    "load problem {} with {} from {} (seed = {}):",
+                    id,
+                    bonus.as_str(),
+                    source,
+                    seed
+                );
+                solve_with_bonus(&problem, seed, budget, id, source, &config, bonus);
+            });
+        }
+        Mode::Collect => {
+            // best の解の頂点を bonus の位置に乗せる
+            let collect_config = CollectConfig::from_args(args)?;
+            args.ids.par_iter().for_each(|&id| {
+                let problem = load_problem(data, id);
+                collect_best(data, &problem, id, &collect_config);
+            });
+        }
+        Mode::BreakALeg => {
+            // 他の問題で BREAK_A_LEG が取れる問題を、辺を 1 本折って解き直す
+            let break_a_leg_config = BreakALegConfig::default();
+            args.ids.par_iter().for_each(|&id| {
+                let source = match find_bonus_source(data, id, BonusType::BreakALeg, MAX_ID) {
+                    Some(source) => source,
+                    None => {
+                        println!("problem {}: no problem gives BREAK_A_LEG", id);
+                        return;
+                    }
+                };
+                let problem = load_problem(data, id);
+                println!(
+                    "load problem {} with BREAK_A_LEG from {} (seed = {}):",
+                    id, source, seed
+                );
+                solve_break_a_leg(
+                    &problem,
+                    seed,
+                    budget,
+                    id,
+                    source,
+                    &config,
+                    &break_a_leg_config,
+                );
+            });
         }
     }
-
-    println!("counter = {}", counter);
-    println!("score: {}", dislike(&problem, &best_solution));
-    let (p0, p1, p2) = penalty(&problem, &best_solution, _problem.epsilon);
-    println!("penalty: {} {} {}", p0, p1, p2);
-
-    let pose = best_solution.to_pose(&problem);
-    if p0 + p1 + p2 < EPS {
-        save_to_best(&problem, &best_solution, problem_id);
-        Some(pose)
-    } else {
-        pose.save_file(format!("data/out/{}.json", problem_id));
-        None
+    // 解き終わった best の頂点を bonus の位置に乗せる
+    if args.flag("collect-bonuses") && mode != Mode::Collect {
+        let collect_config = CollectConfig::from_args(args)?;
+        args.ids.par_iter().for_each(|&id| {
+            let problem = load_problem(data, id);
+            collect_best(data, &problem, id, &collect_config);
+        });
     }
+    Ok(())
 }
 
 fn main() {
-    if false {
-        let id = 15;
-        let problem = Problem::from_file(format!("data/in/{}.json", id).as_str());
-        println!("load problem {}:", id);
-        if let Some(pose) = solve2(&problem, 0, 10000, id) {
-            pose.save_file(format!("data/out/{}.json", id));
-        }
+    let argv = env::args().collect::<Vec<String>>();
+    if argv.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
         return;
     }
-
-    let max_id = 132;
-
-    {
-        // solve
-        (1..=max_id)
-            .collect::<Vec<usize>>()
-            .par_iter()
-            .for_each(|id| {
-                let problem = Problem::from_file(format!("data/in/{}.json", id).as_str());
-                println!("load problem {}:", id);
-                if let Some(_pose) = solve(&problem) {
-                } else if let Some(_pose) = solve2(&problem, 0, 60000, *id) {
-                }
-            });
+    // --data か環境変数 ICFPC_DATA_DIR で data ディレクトリを変えられる
+    let data = DataDir::from_args(&argv);
+    let result =
+        Args::parse_with_flags(&argv[1..], MAX_ID, FLAGS).and_then(|args| run(&args, &data));
+    if let Err(msg) = result {
+        println!("{}\n{}", msg, USAGE);
+        process::exit(1);
     }
 }
@@ -0,0 +1,490 @@
+use crate::{Pos, Solution, SolverProblem};
+use lib::validation::is_within_epsilon;
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+
+// 各近傍を選ぶ確率 (合計が 1 でなくても、比率として扱う)
+#[derive(Clone, Debug)]
+pub struct NeighborhoodConfig {
+    // 1頂点を上下左右に 1 マス移動
+    pub step: f64,
+    // figure の辺の両端点の座標を swap
+    pub swap_edge: f64,
+    // 全体の平行移動
+    pub translate: f64,
+    // 全体の 90 度回転
+    pub rotate: f64,
+    // 全体の反転
+    pub flip: f64,
+    // 隣接頂点から見て許容される距離の格子点に移動
+    pub ring: f64,
+    // 木になっている部分を付け根の頂点周りに回転
+    pub pendant_rotate: f64,
+    // 関節点からぶら下がっている部分を、関節点を通る直線で反転
+    pub articulation_reflect: f64,
+}
+
+impl Default for NeighborhoodConfig {
+    fn default() -> NeighborhoodConfig {
+        NeighborhoodConfig {
+            step: 0.80,
+            swap_edge: 0.05,
+            translate: 0.02,
+            rotate: 0.01,
+            flip: 0.01,
+            ring: 0.07,
+            pendant_rotate: 0.02,
+            articulation_reflect: 0.02,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Move {
+    Step,
+    SwapEdge,
+    Translate,
+    Rotate,
+    Flip,
+    Ring,
+    PendantRotate,
+    ArticulationReflect,
+}
+
+pub const MOVES: [Move; 8] = [
+    Move::Step,
+    Move::SwapEdge,
+    Move::Translate,
+    Move::Rotate,
+    Move::Flip,
+    Move::Ring,
+    Move::PendantRotate,
+    Move::ArticulationReflect,
+];
+
+impl Move {
+    // NeighborhoodConfig のフィールド名
+    pub fn name(&self) -> &'static str {
+        match self {
+            Move::Step => "step",
+            Move::SwapEdge => "swap_edge",
+            Move::Translate => "translate",
+            Move::Rotate => "rotate",
+            Move::Flip => "flip",
+            Move::Ring => "ring",
+            Move::PendantRotate => "pendant_rotate",
+            Move::ArticulationReflect => "articulation_reflect",
+        }
+    }
+
+    // MOVES の中での位置
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl NeighborhoodConfig {
+    // "step=0.8 swap_edge=0.05 ..." の形の要約
+    pub fn summary(&self) -> String {
+        self.weights()
+            .iter()
+            .map(|(m, w)| format!("{}={}", m.name(), w))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn weights(&self) -> [(Move, f64); 8] {
+        [
+            (Move::Step, self.step),
+            (Move::SwapEdge, self.swap_edge),
+            (Move::Translate, self.translate),
+            (Move::Rotate, self.rotate),
+            (Move::Flip, self.flip),
+            (Move::Ring, self.ring),
+            (Move::PendantRotate, self.pendant_rotate),
+            (Move::ArticulationReflect, self.articulation_reflect),
+        ]
+    }
+
+    fn choose<R: Rng>(&self, rng: &mut R) -> Move {
+        let weights = self.weights();
+        let total = weights.iter().map(|(_, w)| w.max(0.0)).sum::<f64>();
+        let mut rest = rng.gen::<f64>() * total;
+        for &(m, w) in weights.iter() {
+            let w = w.max(0.0);
+            if rest < w {
+                return m;
+            }
+            rest -= w;
+        }
+        Move::Step
+    }
+}
+
+// 関節点 pivot を取り除いたときに、pivot にぶら下がる頂点集合
+#[derive(Clone, Debug)]
+pub struct HangingPart {
+    pub pivot: usize,
+    pub members: Vec<usize>,
+    // pivot を含めて木になっているか
+    pub is_tree: bool,
+}
+
+pub fn hanging_parts(neighbors: &[Vec<usize>]) -> Vec<HangingPart> {
+    let n = neighbors.len();
+    let mut ret = vec![];
+
+    for pivot in 0..n {
+        if neighbors[pivot].len() < 2 {
+            continue;
+        }
+        // pivot を除いた連結成分のうち、pivot に隣接するもの
+        let mut component = vec![usize::MAX; n];
+        component[pivot] = pivot;
+        let mut parts: Vec<Vec<usize>> = vec![];
+        for &start in neighbors[pivot].iter() {
+            if component[start] != usize::MAX {
+                continue;
+            }
+            let id = parts.len();
+            let mut members = vec![start];
+            let mut que = VecDeque::new();
+            component[start] = id;
+            que.push_back(start);
+            while let Some(v) = que.pop_front() {
+                for &nv in neighbors[v].iter() {
+                    if component[nv] == usize::MAX {
+                        component[nv] = id;
+                        members.push(nv);
+                        que.push_back(nv);
+                    }
+                }
+            }
+            parts.push(members);
+        }
+        if parts.len() < 2 {
+            continue;
+        }
+
+        // 一番大きい成分を本体とみなし、それ以外がぶら下がっている部分
+        let mut largest = 0;
+        for i in 1..parts.len() {
+            if parts[i].len() > parts[largest].len() {
+                largest = i;
+            }
+        }
+        for (i, members) in parts.into_iter().enumerate() {
+            if i == largest {
+                continue;
+            }
+            let mut edge_count = 0;
+            for &v in members.iter() {
+                for &nv in neighbors[v].iter() {
+                    if nv == pivot {
+                        edge_count += 2;
+                    } else {
+                        edge_count += 1;
+                    }
+                }
+            }
+            edge_count /= 2;
+            ret.push(HangingPart {
+                pivot,
+                is_tree: edge_count == members.len(),
+                members,
+            });
+        }
+    }
+    ret
+}
+
+#[test]
+fn test_hanging_parts() {
+    // 0-1-2-0 の三角形に、2-3-4 の尻尾
+    let neighbors = vec![vec![1, 2], vec![0, 2], vec![1, 0, 3], vec![2, 4], vec![3]];
+    let parts = hanging_parts(&neighbors);
+    assert_eq!(parts.len(), 2);
+
+    assert_eq!(parts[0].pivot, 2);
+    assert_eq!(parts[0].members, vec![3, 4]);
+    assert!(parts[0].is_tree);
+
+    assert_eq!(parts[1].pivot, 3);
+    assert_eq!(parts[1].members, vec![4]);
+    assert!(parts[1].is_tree);
+}
+
+#[test]
+fn test_hanging_parts_cycle() {
+    // 0 を関節点として、三角形 0-1-2 と 0-3-4
+    let neighbors = vec![
+        vec![1, 2, 3, 4],
+        vec![0, 2],
+        vec![1, 0],
+        vec![0, 4],
+        vec![3, 0],
+    ];
+    let parts = hanging_parts(&neighbors);
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0].pivot, 0);
+    assert_eq!(parts[0].members.len(), 2);
+    assert!(!parts[0].is_tree);
+}
+
+// 近傍操作の前の座標を保持しておき、棄却時に戻す
+pub struct Undo {
+    saved: Vec<(usize, Pos)>,
+    kind: Move,
+}
+
+impl Undo {
+    // 適用した近傍の種類
+    pub fn kind(&self) -> Move {
+        self.kind
+    }
+
+    // 近傍操作で動いた頂点
+    pub fn moved_vertices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.saved.iter().map(|&(v, _)| v)
+    }
+
+    pub fn rollback(&self, sol: &mut Solution) {
+        for &(v, p) in self.saved.iter().rev() {
+            sol.vertices[v] = p;
+        }
+    }
+}
+
+pub struct Neighborhood {
+    // 元の距離 (2乗) => 許容される相対座標
+    // BREAK_A_LEG で折った辺の距離は 1/4 刻みなので、4 倍した整数をキーにする
+    ring_offsets: HashMap<i64, Vec<(i64, i64)>>,
+    hanging_parts: Vec<HangingPart>,
+    tree_parts: Vec<usize>,
+    epsilon: f64,
+}
+
+fn ring_key(orig_dist: f64) -> i64 {
+    (orig_dist * 4.0).round() as i64
+}
+
+impl Neighborhood {
+    pub fn new(problem: &SolverProblem, epsilon: f64) -> Neighborhood {
+        let mut ring_offsets = HashMap::new();
+        for &(v1, v2) in problem.figure_edges.iter() {
+            let orig_dist = problem.figure_distance(v1, v2);
+            ring_offsets.entry(ring_key(orig_dist)).or_insert_with(|| {
+                let r = (orig_dist * (1.0 + epsilon)).sqrt().ceil() as i64;
+                let mut offsets = vec![];
+                for dy in -r..=r {
+                    for dx in -r..=r {
+                        if is_within_epsilon(epsilon, orig_dist, (dy * dy + dx * dx) as f64) {
+                            offsets.push((dx, dy));
+                        }
+                    }
+                }
+                offsets
+            });
+        }
+
+        let hanging_parts = hanging_parts(&problem.figure_neighbors);
+        let tree_parts = (0..hanging_parts.len())
+            .filter(|&i| hanging_parts[i].is_tree)
+            .collect::<Vec<usize>>();
+
+        Neighborhood {
+            ring_offsets,
+            hanging_parts,
+            tree_parts,
+            epsilon,
+        }
+    }
+
+    // 近傍を 1 つ選んで適用する
+    // 適用できなかった場合は解を変更せず None を返す
+    pub fn apply<R: Rng>(
+        &self,
+        config: &NeighborhoodConfig,
+        problem: &SolverProblem,
+        sol: &mut Solution,
+        rng: &mut R,
+    ) -> Option<Undo> {
+        let n = sol.vertices.len();
+        let mut next = vec![];
+
+        let kind = config.choose(rng);
+        match kind {
+            Move::Step => {
+                let dy = [-1, 0, 1, 0];
+                let dx = [0, 1, 0, -1];
+                let v = rng.gen::<usize>() % n;
+                let dir = rng.gen::<usize>() % 4;
+                let p = sol.vertices[v];
+                next.push((v, Pos::new(p.x + dx[dir], p.y + dy[dir])));
+            }
+            Move::SwapEdge => {
+                if problem.figure_edges.is_empty() {
+                    return None;
+                }
+                let (v1, v2) =
+                    problem.figure_edges[rng.gen::<usize>() % problem.figure_edges.len()];
+                next.push((v1, sol.vertices[v2]));
+                next.push((v2, sol.vertices[v1]));
+            }
+            Move::Translate => {
+                let dx = rng.gen_range(-2..=2);
+                let dy = rng.gen_range(-2..=2);
+                if dx == 0 && dy == 0 {
+                    return None;
+                }
+                for (v, p) in sol.vertices.iter().enumerate() {
+                    next.push((v, Pos::new(p.x + dx, p.y + dy)));
+                }
+            }
+            Move::Rotate => {
+                let center = center_of(&sol.vertices);
+                let times = rng.gen_range(1..=3);
+                for (v, p) in sol.vertices.iter().enumerate() {
+                    let mut rx = p.x - center.x;
+                    let mut ry = p.y - center.y;
+                    for _ in 0..times {
+                        let tmp = rx;
+                        rx = -ry;
+                        ry = tmp;
+                    }
+                    next.push((v, Pos::new(center.x + rx, center.y + ry)));
+                }
+            }
+            Move::Flip => {
+                let center = center_of(&sol.vertices);
+                let horizontal = rng.gen::<bool>();
+                for (v, p) in sol.vertices.iter().enumerate() {
+                    if horizontal {
+                        next.push((v, Pos::new(2 * center.x - p.x, p.y)));
+                    } else {
+                        next.push((v, Pos::new(p.x, 2 * center.y - p.y)));
+                    }
+                }
+            }
+            Move::Ring => {
+                let v = rng.gen::<usize>() % n;
+                let p = self.ring_position(problem, sol, v, rng)?;
+                next.push((v, p));
+            }
+            Move::PendantRotate => {
+                if self.tree_parts.is_empty() {
+                    return None;
+                }
+                let part = &self.hanging_parts
+                    [self.tree_parts[rng.gen::<usize>() % self.tree_parts.len()]];
+                let center = sol.vertices[part.pivot];
+                let angle = rng.gen_range(-std::f64::consts::PI..std::f64::consts::PI);
+                let (sin, cos) = angle.sin_cos();
+                for &v in part.members.iter() {
+                    let rx = (sol.vertices[v].x - center.x) as f64;
+                    let ry = (sol.vertices[v].y - center.y) as f64;
+                    let nx = (rx * cos - ry * sin).round() as i64;
+                    let ny = (rx * sin + ry * cos).round() as i64;
+                    next.push((v, Pos::new(center.x + nx, center.y + ny)));
+                }
+            }
+            Move::ArticulationReflect => {
+                if self.hanging_parts.is_empty() {
+                    return None;
+                }
+                let part = &self.hanging_parts[rng.gen::<usize>() % self.hanging_parts.len()];
+                let center = sol.vertices[part.pivot];
+                // 格子点が格子点に移るように、縦・横・斜め 2 方向の直線に限定
+                let axis = rng.gen::<usize>() % 4;
+                for &v in part.members.iter() {
+                    let rx = sol.vertices[v].x - center.x;
+                    let ry = sol.vertices[v].y - center.y;
+                    let (nx, ny) = match axis {
+                        0 => (rx, -ry),
+                        1 => (-rx, ry),
+                        2 => (ry, rx),
+                        _ => (-ry, -rx),
+                    };
+                    next.push((v, Pos::new(center.x + nx, center.y + ny)));
+                }
+            }
+        }
+
+        for (_, p) in next.iter() {
+            if !problem.is_inside_grid(p) {
+                return None;
+            }
+        }
+
+        let mut saved = Vec::with_capacity(next.len());
+        for (v, p) in next.into_iter() {
+            saved.push((v, sol.vertices[v]));
+            sol.vertices[v] = p;
+        }
+        Some(Undo { saved, kind })
+    }
+
+    // v の隣接頂点のいずれかから見て許容範囲の距離にある格子点を選ぶ
+    // 全ての隣接頂点について許容範囲にある点があれば、そちらを優先する
+    fn ring_position<R: Rng>(
+        &self,
+        problem: &SolverProblem,
+        sol: &Solution,
+        v: usize,
+        rng: &mut R,
+    ) -> Option<Pos> {
+        let neighbors = &problem.figure_neighbors[v];
+        if neighbors.is_empty() {
+            return None;
+        }
+        let base = neighbors[rng.gen::<usize>() % neighbors.len()];
+        let offsets = &self.ring_offsets[&ring_key(problem.figure_distance(v, base))];
+        let center = sol.vertices[base];
+
+        // reservoir sampling で 1 点ずつ選ぶ
+        let mut any_candidate = None;
+        let mut any_count = 0;
+        let mut all_candidate = None;
+        let mut all_count = 0;
+        for &(dx, dy) in offsets.iter() {
+            let p = Pos::new(center.x + dx, center.y + dy);
+            if !problem.is_inside_grid(&p) || (p.x == sol.vertices[v].x && p.y == sol.vertices[v].y)
+            {
+                continue;
+            }
+            any_count += 1;
+            if rng.gen::<usize>() % any_count == 0 {
+                any_candidate = Some(p);
+            }
+            let fits_all = neighbors.iter().all(|&nv| {
+                is_within_epsilon(
+                    self.epsilon,
+                    problem.figure_distance(v, nv),
+                    p.distance(&sol.vertices[nv]) as f64,
+                )
+            });
+            if fits_all {
+                all_count += 1;
+                if rng.gen::<usize>() % all_count == 0 {
+                    all_candidate = Some(p);
+                }
+            }
+        }
+        all_candidate.or(any_candidate)
+    }
+}
+
+// 座標の平均を格子点に丸めたもの
+fn center_of(vertices: &[Pos]) -> Pos {
+    let mut sx = 0;
+    let mut sy = 0;
+    for p in vertices.iter() {
+        sx += p.x;
+        sy += p.y;
+    }
+    let n = vertices.len() as f64;
+    Pos::new(
+        (sx as f64 / n).round() as i64,
+        (sy as f64 / n).round() as i64,
+    )
+}
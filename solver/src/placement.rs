@@ -0,0 +1,192 @@
+use crate::{dislike, hole_penalty, Pos, Solution, SolverProblem};
+
+// figure を 8 通りの向き (回転・反転) と平行移動で置いてみて、良いものを初期解にする
+#[derive(Clone, Debug)]
+pub struct PlacementConfig {
+    // 平行移動を試す間隔
+    pub translation_step: i64,
+    // 1 方向あたりに試す平行移動の上限 (大きい問題では間隔を広げる)
+    pub max_translations: i64,
+    // 返す初期解の数
+    pub candidates: usize,
+}
+
+impl Default for PlacementConfig {
+    fn default() -> PlacementConfig {
+        PlacementConfig {
+            translation_step: 2,
+            max_translations: 64,
+            candidates: 4,
+        }
+    }
+}
+
+// 元の figure の重心を中心にした 8 通りの向き
+fn orientations(problem: &SolverProblem) -> Vec<Vec<Pos>> {
+    let vertices = &problem.orig_figure_vertices;
+    let n = vertices.len() as i64;
+    let cx = vertices.iter().map(|p| p.x).sum::<i64>() / n;
+    let cy = vertices.iter().map(|p| p.y).sum::<i64>() / n;
+
+    let mut ret = vec![];
+    for flip in 0..2 {
+        for rotate in 0..4 {
+            let oriented = vertices
+                .iter()
+                .map(|p| {
+                    let (mut dx, mut dy) = (p.x - cx, p.y - cy);
+                    if flip == 1 {
+                        dx = -dx;
+                    }
+                    for _ in 0..rotate {
+                        let t = dx;
+                        dx = -dy;
+                        dy = t;
+                    }
+                    Pos::new(cx + dx, cy + dy)
+                })
+                .collect::<Vec<Pos>>();
+            ret.push(oriented);
+        }
+    }
+    ret
+}
+
+// 平行移動を除いて同じ形になる向きは 1 つにまとめる (figure の対称性)
+fn canonical_edges(problem: &SolverProblem, vertices: &[Pos]) -> Vec<(i64, i64, i64, i64)> {
+    let min_x = vertices.iter().map(|p| p.x).min().unwrap();
+    let min_y = vertices.iter().map(|p| p.y).min().unwrap();
+    let mut edges = problem
+        .figure_edges
+        .iter()
+        .map(|&(a, b)| {
+            let pa = (vertices[a].x - min_x, vertices[a].y - min_y);
+            let pb = (vertices[b].x - min_x, vertices[b].y - min_y);
+            let (pa, pb) = if pa <= pb { (pa, pb) } else { (pb, pa) };
+            (pa.0, pa.1, pb.0, pb.1)
+        })
+        .collect::<Vec<_>>();
+    edges.sort();
+    edges
+}
+
+// 平行移動の候補。grid からはみ出さない範囲を等間隔に区切る
+fn translations(lower: i64, upper: i64, config: &PlacementConfig) -> Vec<i64> {
+    if lower > upper {
+        return vec![];
+    }
+    let range = upper - lower;
+    let step = config
+        .translation_step
+        .max(1)
+        .max((range + config.max_translations - 1) / config.max_translations.max(1));
+    let mut ret = (0..)
+        .map(|i| lower + i * step)
+        .take_while(|&t| t <= upper)
+        .collect::<Vec<i64>>();
+    if *ret.last().unwrap() != upper {
+        ret.push(upper);
+    }
+    ret
+}
+
+// 速く計算できる項 (はみ出しと dislike) だけで評価する
+// 辺の長さは回転・反転・平行移動で変わらないので見なくてよい
+fn placement_score(problem: &SolverProblem, solution: &Solution) -> f64 {
+    dislike(problem, solution) + hole_penalty(problem, solution) * 100.0
+}
+
+pub fn search_placements(problem: &SolverProblem, config: &PlacementConfig) -> Vec<Solution> {
+    let mut seen = vec![];
+    let mut results: Vec<(f64, Solution)> = vec![];
+
+    for oriented in orientations(problem) {
+        let canonical = canonical_edges(problem, &oriented);
+        if seen.contains(&canonical) {
+            continue;
+        }
+        seen.push(canonical);
+
+        let min_x = oriented.iter().map(|p| p.x).min().unwrap();
+        let max_x = oriented.iter().map(|p| p.x).max().unwrap();
+        let min_y = oriented.iter().map(|p| p.y).min().unwrap();
+        let max_y = oriented.iter().map(|p| p.y).max().unwrap();
+        let xs = translations(-min_x, problem.width as i64 - 1 - max_x, config);
+        let ys = translations(-min_y, problem.height as i64 - 1 - max_y, config);
+
+        // この向きで最も良い位置
+        let mut best: Option<(f64, Solution)> = None;
+        for &dy in ys.iter() {
+            for &dx in xs.iter() {
+                let solution = Solution {
+                    vertices: oriented
+                        .iter()
+                        .map(|p| Pos::new(p.x + dx, p.y + dy))
+                        .collect(),
+                };
+                let score = placement_score(problem, &solution);
+                let improved = match &best {
+                    Some((best_score, _)) => score < *best_score,
+                    None => true,
+                };
+                if improved {
+                    best = Some((score, solution));
+                }
+            }
+        }
+        if let Some(best) = best {
+            results.push(best);
+        }
+    }
+
+    results.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    let mut ret = results
+        .into_iter()
+        .take(config.candidates.max(1))
+        .map(|(_, solution)| solution)
+        .collect::<Vec<Solution>>();
+    if ret.is_empty() {
+        // どの向きも grid に収まらなければ元の figure のまま
+        ret.push(Solution::new(&problem.orig_figure_vertices));
+    }
+    ret
+}
+
+#[test]
+fn test_search_placements() {
+    use lib::data::{Point, Problem};
+
+    // 正方形の穴に、同じ大きさの正方形の figure
+    let mut problem = Problem::new();
+    for &(x, y) in [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)].iter() {
+        problem.hole.push(Point::new(x, y));
+    }
+    for &(x, y) in [(3.0, 3.0), (13.0, 3.0), (13.0, 13.0), (3.0, 13.0)].iter() {
+        problem.figure.push(Point::new(x, y));
+    }
+    for i in 0..4 {
+        problem.figure.connect(i, (i + 1) % 4);
+    }
+    let problem = SolverProblem::new(&problem);
+
+    // 正方形は 8 通りとも同じ形
+    let distinct = orientations(&problem)
+        .iter()
+        .map(|vertices| canonical_edges(&problem, vertices))
+        .fold(vec![], |mut acc, edges| {
+            if !acc.contains(&edges) {
+                acc.push(edges);
+            }
+            acc
+        });
+    assert_eq!(distinct.len(), 1);
+
+    let config = PlacementConfig {
+        translation_step: 1,
+        ..PlacementConfig::default()
+    };
+    let solutions = search_placements(&problem, &config);
+    assert_eq!(solutions.len(), 1);
+    assert_eq!(dislike(&problem, &solutions[0]), 0.0);
+    assert_eq!(hole_penalty(&problem, &solutions[0]), 0.0);
+}
@@ -0,0 +1,147 @@
+use crate::neighborhood::Neighborhood;
+use crate::{dislike, save_to_best, Budget, Pos, Solution, SolverConfig, SolverProblem};
+use lib::data::{Point, Pose, Problem};
+use lib::validation::{is_acceptable_length, is_inside_hole, is_segment_inside_hole, validate};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::path::Path;
+use std::time::Instant;
+
+// valid な解から始めて、公式ルールで valid な状態だけを渡り歩いて dislike を下げる
+#[derive(Clone, Debug, Default)]
+pub struct PolishConfig {
+    // dislike が悪化する遷移を受け入れる温度。0 なら山登り
+    pub temperature: f64,
+}
+
+fn to_point(problem: &SolverProblem, p: &Pos) -> Point {
+    Point::new(
+        (p.x + problem.offset_x) as f64,
+        (p.y + problem.offset_y) as f64,
+    )
+}
+
+// 動いた頂点と、それに接続する辺だけを公式ルールで検証する
+fn is_valid_around<I: Iterator<Item = usize>>(
+    _problem: &Problem,
+    problem: &SolverProblem,
+    sol: &Solution,
+    moved: I,
+) -> bool {
+    for v in moved {
+        let p = to_point(problem, &sol.vertices[v]);
+        if !is_inside_hole(&_problem.hole, &p) {
+            return false;
+        }
+        for &nv in problem.figure_neighbors[v].iter() {
+            let orig_dist = problem.figure_distance(v, nv);
+            let cur_dist = sol.vertices[v].distance(&sol.vertices[nv]) as f64;
+            if !is_acceptable_length(_problem, orig_dist, cur_dist) {
+                return false;
+            }
+            let np = to_point(problem, &sol.vertices[nv]);
+            if !is_segment_inside_hole(&_problem.hole, &p, &np) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+pub fn polish(
+    _problem: &Problem,
+    seed: u64,
+    budget: Budget,
+    problem_id: usize,
+    config: &SolverConfig,
+    polish: &PolishConfig,
+) -> Option<Pose> {
+    let best_filepath = config.data.best(problem_id);
+    if !Path::new(best_filepath.as_str()).exists() {
+        println!("no best pose for problem {}", problem_id);
+        return None;
+    }
+    let start_pose = Pose::from_file(best_filepath.as_str());
+    if !validate(_problem, &start_pose).is_valid() {
+        println!("best pose of problem {} is invalid", problem_id);
+        return None;
+    }
+    // bonus を使った解は、bonus なしの検証では直せないので触らない
+    if !start_pose.bonuses.is_empty() {
+        println!("best pose of problem {} uses a bonus", problem_id);
+        return None;
+    }
+
+    let problem = SolverProblem::new(_problem);
+    let neighborhood = Neighborhood::new(&problem, _problem.epsilon);
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let mut current_solution = Solution::from_pose(&problem, &start_pose);
+    let mut current_dislike = dislike(&problem, &current_solution);
+    let start_dislike = current_dislike;
+
+    let mut best_solution = current_solution.clone();
+    let mut best_dislike = current_dislike;
+
+    let timer = Instant::now();
+    let mut counter = 0;
+    loop {
+        if let Some(undo) = neighborhood.apply(
+            &config.neighborhood,
+            &problem,
+            &mut current_solution,
+            &mut rng,
+        ) {
+            let mut accepted = false;
+            if is_valid_around(_problem, &problem, &current_solution, undo.moved_vertices()) {
+                let after_dislike = dislike(&problem, &current_solution);
+                let de = after_dislike - current_dislike;
+                if de <= 0.0
+                    || (polish.temperature > 0.0
+                        && rng.gen::<f64>() < (-de / polish.temperature).exp())
+                {
+                    accepted = true;
+                    current_dislike = after_dislike;
+                    if best_dislike > current_dislike {
+                        best_dislike = current_dislike;
+                        best_solution = current_solution.clone();
+                    }
+                }
+            }
+            if !accepted {
+                undo.rollback(&mut current_solution);
+            }
+        }
+
+        counter += 1;
+        if counter % 1024 == 1023 && budget.progress(counter, &timer).is_none() {
+            break;
+        }
+    }
+
+    println!("counter = {}", counter);
+    println!("seed = {}", seed);
+    println!(
+        "polish problem {}: {} -> {}",
+        problem_id, start_dislike, best_dislike
+    );
+
+    if best_dislike >= start_dislike {
+        return None;
+    }
+    // 動いた頂点の周りしか見ていないので、save_to_best で全体を検証し直す
+    let saved = save_to_best(
+        config,
+        _problem,
+        &problem,
+        &best_solution,
+        problem_id,
+        seed,
+        "polish",
+    );
+    if saved {
+        Some(best_solution.to_pose(&problem))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,404 @@
+use crate::{evaluate_all, Pos, Solution, SolverProblem};
+use lib::validation::is_within_epsilon;
+use std::collections::VecDeque;
+
+// バネモデルによる連続座標での緩和
+#[derive(Clone, Debug)]
+pub struct RelaxConfig {
+    pub iterations: usize,
+    // 1 反復での移動量の係数
+    pub step: f64,
+    // 辺の長さを許容範囲に戻す力
+    pub spring: f64,
+    // hole の外にいる頂点を内側に押し戻す力
+    pub wall: f64,
+    // hole の頂点が、最も近い figure の頂点を引き寄せる力
+    pub attraction: f64,
+    // 整数座標に丸めた後の修復を何周するか
+    pub repair_rounds: usize,
+}
+
+impl Default for RelaxConfig {
+    fn default() -> RelaxConfig {
+        RelaxConfig {
+            iterations: 2000,
+            step: 0.1,
+            spring: 1.0,
+            wall: 1.0,
+            attraction: 0.05,
+            repair_rounds: 50,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Vec2 {
+    x: f64,
+    y: f64,
+}
+
+impl Vec2 {
+    fn new(x: f64, y: f64) -> Vec2 {
+        Vec2 { x, y }
+    }
+}
+
+// hole からの距離 (2乗ではない) を双線形補間したもの
+fn hole_distance_at(problem: &SolverProblem, x: f64, y: f64) -> f64 {
+    let x = x.max(0.0).min((problem.width - 1) as f64);
+    let y = y.max(0.0).min((problem.height - 1) as f64);
+    let x0 = x.floor() as usize;
+    let y0 = y.floor() as usize;
+    let x1 = (x0 + 1).min(problem.width - 1);
+    let y1 = (y0 + 1).min(problem.height - 1);
+    let fx = x - x0 as f64;
+    let fy = y - y0 as f64;
+    let d = |y: usize, x: usize| (problem.hole_distance[y][x] as f64).sqrt();
+
+    let top = d(y0, x0) * (1.0 - fx) + d(y0, x1) * fx;
+    let bottom = d(y1, x0) * (1.0 - fx) + d(y1, x1) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+// hole の外側での距離場の勾配。内側では 0
+fn hole_distance_gradient(problem: &SolverProblem, x: f64, y: f64) -> Vec2 {
+    if hole_distance_at(problem, x, y) <= 0.0 {
+        return Vec2::new(0.0, 0.0);
+    }
+    let h = 0.5;
+    let gx =
+        (hole_distance_at(problem, x + h, y) - hole_distance_at(problem, x - h, y)) / (2.0 * h);
+    let gy =
+        (hole_distance_at(problem, x, y + h) - hole_distance_at(problem, x, y - h)) / (2.0 * h);
+    Vec2::new(gx, gy)
+}
+
+// 辺の途中の点が hole の外に出ていないかを調べる分割数
+const EDGE_SAMPLES: usize = 4;
+
+// 辺 1 本の、許容範囲からのはみ出し量。判定は validation と同じ is_within_epsilon で行う
+// 境界で浮動小数の rate が epsilon 以下になっても、範囲外なら 0 にはしない
+fn length_excess(epsilon: f64, orig_dist: f64, cur_dist: f64) -> f64 {
+    if is_within_epsilon(epsilon, orig_dist, cur_dist) {
+        0.0
+    } else {
+        f64::max((cur_dist / orig_dist - 1.0).abs() - epsilon, f64::EPSILON)
+    }
+}
+
+// 頂点 v に接続する辺の、許容範囲からのはみ出し量の合計
+fn edge_violation(problem: &SolverProblem, sol: &Solution, v: usize, p: &Pos, epsilon: f64) -> f64 {
+    let mut sum = 0.0;
+    for &nv in problem.figure_neighbors[v].iter() {
+        let orig_dist = problem.figure_distance(v, nv);
+        sum += length_excess(epsilon, orig_dist, p.distance(&sol.vertices[nv]) as f64);
+    }
+    sum
+}
+
+pub fn count_violated_edges(problem: &SolverProblem, sol: &Solution, epsilon: f64) -> usize {
+    problem
+        .figure_edges
+        .iter()
+        .filter(|&&(v1, v2)| {
+            !is_within_epsilon(
+                epsilon,
+                problem.figure_distance(v1, v2),
+                sol.vertices[v1].distance(&sol.vertices[v2]) as f64,
+            )
+        })
+        .count()
+}
+
+fn relax_continuous(
+    problem: &SolverProblem,
+    init: &Solution,
+    epsilon: f64,
+    config: &RelaxConfig,
+) -> Vec<Vec2> {
+    let n = init.vertices.len();
+    let mut pos = init
+        .vertices
+        .iter()
+        .map(|p| Vec2::new(p.x as f64, p.y as f64))
+        .collect::<Vec<Vec2>>();
+
+    // 丸めたときに余裕があるように、許容範囲の半分を目標にする
+    let targets = problem
+        .figure_edges
+        .iter()
+        .map(|&(v1, v2)| {
+            let orig_dist = problem.figure_distance(v1, v2);
+            (
+                (orig_dist * (1.0 - epsilon * 0.5)).sqrt(),
+                (orig_dist * (1.0 + epsilon * 0.5)).sqrt(),
+            )
+        })
+        .collect::<Vec<(f64, f64)>>();
+
+    for iter in 0..config.iterations {
+        let mut force = vec![Vec2::new(0.0, 0.0); n];
+        // 最後の 1/4 は引き寄せをやめて、辺の長さを落ち着かせる
+        let attraction = if iter * 4 < config.iterations * 3 {
+            config.attraction
+        } else {
+            0.0
+        };
+
+        // 辺のバネ
+        for (e, &(v1, v2)) in problem.figure_edges.iter().enumerate() {
+            let dx = pos[v2].x - pos[v1].x;
+            let dy = pos[v2].y - pos[v1].y;
+            let len = (dx * dx + dy * dy).sqrt();
+            if len < 1e-9 {
+                continue;
+            }
+            let (min_len, max_len) = targets[e];
+            let target = len.max(min_len).min(max_len);
+            let f = config.spring * (len - target) / len;
+            force[v1].x += f * dx;
+            force[v1].y += f * dy;
+            force[v2].x -= f * dx;
+            force[v2].y -= f * dy;
+        }
+
+        // hole の外側にいる頂点は、距離場の勾配に沿って押し戻す
+        for v in 0..n {
+            let g = hole_distance_gradient(problem, pos[v].x, pos[v].y);
+            force[v].x -= config.wall * g.x;
+            force[v].y -= config.wall * g.y;
+        }
+        // 辺の途中が hole の外に出ている場合は、両端点を押し戻す
+        for &(v1, v2) in problem.figure_edges.iter() {
+            for k in 1..EDGE_SAMPLES {
+                let t = k as f64 / EDGE_SAMPLES as f64;
+                let x = pos[v1].x * (1.0 - t) + pos[v2].x * t;
+                let y = pos[v1].y * (1.0 - t) + pos[v2].y * t;
+                let g = hole_distance_gradient(problem, x, y);
+                force[v1].x -= config.wall * g.x * (1.0 - t);
+                force[v1].y -= config.wall * g.y * (1.0 - t);
+                force[v2].x -= config.wall * g.x * t;
+                force[v2].y -= config.wall * g.y * t;
+            }
+        }
+
+        // hole の頂点は、最も近い figure の頂点を引き寄せる
+        for hv in problem.hole_vertices.iter() {
+            let hx = hv.x as f64;
+            let hy = hv.y as f64;
+            let mut nearest = 0;
+            let mut min_dist = f64::MAX;
+            for (v, p) in pos.iter().enumerate() {
+                let d = (p.x - hx) * (p.x - hx) + (p.y - hy) * (p.y - hy);
+                if d < min_dist {
+                    min_dist = d;
+                    nearest = v;
+                }
+            }
+            force[nearest].x += attraction * (hx - pos[nearest].x);
+            force[nearest].y += attraction * (hy - pos[nearest].y);
+        }
+
+        // 1 反復で大きく飛ばないように、移動量は 1 以下に抑える
+        for v in 0..n {
+            let mut mx = force[v].x * config.step;
+            let mut my = force[v].y * config.step;
+            let norm = (mx * mx + my * my).sqrt();
+            if norm > 1.0 {
+                mx /= norm;
+                my /= norm;
+            }
+            pos[v].x = (pos[v].x + mx).max(0.0).min((problem.width - 1) as f64);
+            pos[v].y = (pos[v].y + my).max(0.0).min((problem.height - 1) as f64);
+        }
+    }
+    pos
+}
+
+// BFS 順に 1 頂点ずつ、配置済みの隣接頂点との辺の長さが許容範囲に入る格子点に置く
+fn snap(problem: &SolverProblem, pos: &[Vec2], epsilon: f64) -> Solution {
+    let n = pos.len();
+    let mut sol = Solution {
+        vertices: pos
+            .iter()
+            .map(|p| Pos::new(p.x.round() as i64, p.y.round() as i64))
+            .collect(),
+    };
+
+    let mut placed = vec![false; n];
+    for start in 0..n {
+        if placed[start] {
+            continue;
+        }
+        placed[start] = true;
+        let mut que = VecDeque::new();
+        que.push_back(start);
+        while let Some(v) = que.pop_front() {
+            for &nv in problem.figure_neighbors[v].iter() {
+                if placed[nv] {
+                    continue;
+                }
+                let target = pos[nv];
+                let violation = |p: &Pos| -> f64 {
+                    let mut sum = 0.0;
+                    for &nnv in problem.figure_neighbors[nv].iter() {
+                        if !placed[nnv] {
+                            continue;
+                        }
+                        let orig_dist = problem.figure_distance(nv, nnv);
+                        let cur_dist = p.distance(&sol.vertices[nnv]) as f64;
+                        sum += length_excess(epsilon, orig_dist, cur_dist);
+                    }
+                    sum
+                };
+                // 許容範囲に入る点が見つかるまで、探索範囲を広げる
+                let mut best = sol.vertices[nv];
+                let mut best_key = (violation(&best), 0.0);
+                for radius in 1..=SNAP_RADIUS {
+                    let cx = target.x.round() as i64;
+                    let cy = target.y.round() as i64;
+                    for y in cy - radius..=cy + radius {
+                        for x in cx - radius..=cx + radius {
+                            let p = Pos::new(x, y);
+                            if !problem.is_inside_grid(&p) {
+                                continue;
+                            }
+                            let dx = x as f64 - target.x;
+                            let dy = y as f64 - target.y;
+                            let key = (violation(&p), dx * dx + dy * dy);
+                            if key < best_key {
+                                best_key = key;
+                                best = p;
+                            }
+                        }
+                    }
+                    if best_key.0 == 0.0 {
+                        break;
+                    }
+                }
+                sol.vertices[nv] = best;
+                placed[nv] = true;
+                que.push_back(nv);
+            }
+        }
+    }
+    sol
+}
+
+// snap で探す、連続解の周りの範囲
+const SNAP_RADIUS: i64 = 4;
+
+// 丸めた後、辺の長さが許容範囲を外れる頂点を周囲の格子点に動かして修復する
+fn round_and_repair(
+    problem: &SolverProblem,
+    pos: &[Vec2],
+    epsilon: f64,
+    config: &RelaxConfig,
+) -> Solution {
+    let mut sol = snap(problem, pos, epsilon);
+
+    let radius = 3;
+    for _ in 0..config.repair_rounds {
+        let mut changed = false;
+        for (v, target) in pos.iter().enumerate() {
+            let cur = sol.vertices[v];
+            if edge_violation(problem, &sol, v, &cur, epsilon) == 0.0 {
+                continue;
+            }
+            // 辺のはみ出し -> hole からの距離 -> 連続解からのずれ の順で比較
+            let key = |p: &Pos| -> (f64, usize, f64) {
+                let dx = p.x as f64 - target.x;
+                let dy = p.y as f64 - target.y;
+                (
+                    edge_violation(problem, &sol, v, p, epsilon),
+                    problem.hole_distance[p.y as usize][p.x as usize],
+                    dx * dx + dy * dy,
+                )
+            };
+            let mut best = cur;
+            let mut best_key = key(&cur);
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let p = Pos::new(cur.x + dx, cur.y + dy);
+                    if !problem.is_inside_grid(&p) {
+                        continue;
+                    }
+                    let k = key(&p);
+                    if k < best_key {
+                        best_key = k;
+                        best = p;
+                    }
+                }
+            }
+            if best.x != cur.x || best.y != cur.y {
+                sol.vertices[v] = best;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    sol
+}
+
+// 連続緩和してから整数座標に戻す
+// 元の解より評価値が悪くなった場合は、元の解を返す
+pub fn relax(
+    problem: &SolverProblem,
+    init: &Solution,
+    epsilon: f64,
+    config: &RelaxConfig,
+) -> Solution {
+    let pos = relax_continuous(problem, init, epsilon, config);
+    let sol = round_and_repair(problem, &pos, epsilon, config);
+
+    let init_eval = evaluate_all(problem, init, epsilon);
+    let relaxed_eval = evaluate_all(problem, &sol, epsilon);
+    println!(
+        "relax: violated edges {} -> {}, eval {} -> {}",
+        count_violated_edges(problem, init, epsilon),
+        count_violated_edges(problem, &sol, epsilon),
+        init_eval,
+        relaxed_eval
+    );
+    if relaxed_eval < init_eval {
+        sol
+    } else {
+        init.clone()
+    }
+}
+
+#[test]
+fn test_relax_keeps_edges() {
+    use lib::data::{Point, Problem};
+
+    let mut problem = Problem::new();
+    for &(x, y) in [(0.0, 0.0), (30.0, 0.0), (30.0, 30.0), (0.0, 30.0)].iter() {
+        problem.hole.push(Point::new(x, y));
+    }
+    // 重心を合わせると hole からはみ出す三角形
+    for &(x, y) in [(0.0, 0.0), (24.0, 0.0), (24.0, 24.0)].iter() {
+        problem.figure.push(Point::new(x, y));
+    }
+    problem.figure.connect(0, 1);
+    problem.figure.connect(1, 2);
+    problem.figure.connect(2, 0);
+    problem.epsilon = 0.05;
+
+    let solver_problem = SolverProblem::new(&problem);
+    let init = Solution::new(&solver_problem.orig_figure_vertices);
+    let sol = relax(
+        &solver_problem,
+        &init,
+        problem.epsilon,
+        &RelaxConfig::default(),
+    );
+    assert_eq!(
+        count_violated_edges(&solver_problem, &sol, problem.epsilon),
+        0
+    );
+    for p in sol.vertices.iter() {
+        assert_eq!(solver_problem.hole_distance[p.y as usize][p.x as usize], 0);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{solve2, Budget, SolverConfig};
+use lib::data::{Pose, Problem};
+use lib::paths::DataDir;
+use lib::score::score_potential;
+use lib::validation::{dislike, validate};
+use rayon::prelude::*;
+use std::path::Path;
+use std::time::Instant;
+
+// 全体の時間を、得点が伸びそうな問題から順に短い時間ずつ配る
+#[derive(Clone, Debug)]
+pub struct ScheduleConfig {
+    // 全体の時間 (ミリ秒)
+    pub total: u128,
+    // 1 回に 1 問へ使う時間 (ミリ秒)
+    pub slice: u128,
+    // 同時に解く問題の数
+    pub parallel: usize,
+    // 1 回解くと残りの得点のうちこれだけ取れると仮定する (まだ解いていないときの見積もり)
+    pub gain_rate: f64,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> ScheduleConfig {
+        ScheduleConfig {
+            total: 30 * 60 * 1000,
+            slice: 20000,
+            parallel: rayon::current_num_threads(),
+            gain_rate: 0.3,
+        }
+    }
+}
+
+struct Task {
+    id: usize,
+    problem: Problem,
+    // best の valid な解の dislike
+    dislike: Option<f64>,
+    runs: usize,
+    improvements: usize,
+}
+
+impl Task {
+    // 1 秒あたりに増える得点の見積もり
+    // 残りの得点は dislike と得点の式 (問題の大きさを含む) から決まる
+    // 改善できる確率を (improvements + 1) / (runs + 2) で見積もり、改善しなければ下がっていく
+    // まだ解いていないとき (確率 1/2) にちょうど gain_rate になるように 2 倍する
+    fn expected_gain_rate(&self, config: &ScheduleConfig) -> f64 {
+        let potential = score_potential(&self.problem, self.dislike);
+        let success = (self.improvements + 1) as f64 / (self.runs + 2) as f64;
+        potential * config.gain_rate * success * 2.0 / (config.slice as f64 / 1000.0)
+    }
+}
+
+fn best_dislike(data: &DataDir, problem: &Problem, problem_id: usize) -> Option<f64> {
+    let best_filepath = data.best(problem_id);
+    if !Path::new(best_filepath.as_str()).exists() {
+        return None;
+    }
+    let pose = Pose::from_file(best_filepath.as_str());
+    if validate(problem, &pose).is_valid() {
+        Some(dislike(problem, &pose))
+    } else {
+        None
+    }
+}
+
+struct Scheduler {
+    tasks: Vec<Task>,
+}
+
+impl Scheduler {
+    // 次に解く問題を、見積もりの高い順に count 個まで選ぶ。もう得点が増えない問題は選ばない
+    fn next(&self, count: usize, config: &ScheduleConfig) -> Vec<usize> {
+        let mut order = (0..self.tasks.len())
+            .map(|i| (self.tasks[i].expected_gain_rate(config), i))
+            .filter(|&(rate, _)| rate > 0.0)
+            .collect::<Vec<(f64, usize)>>();
+        order.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+        order.into_iter().take(count).map(|(_, i)| i).collect()
+    }
+
+    // 解いた結果で見積もりを更新する
+    fn report(&mut self, index: usize, dislike: Option<f64>) {
+        let task = &mut self.tasks[index];
+        task.runs += 1;
+        let improved = match (dislike, task.dislike) {
+            (Some(new), Some(old)) => new < old,
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+        if improved {
+            task.improvements += 1;
+            task.dislike = dislike;
+        }
+    }
+}
+
+#[cfg(test)]
+fn test_task(id: usize, dislike: Option<f64>) -> Task {
+    Task {
+        id,
+        problem: Problem::from_file(DataDir::workspace().problem(1).as_str()),
+        dislike,
+        runs: 0,
+        improvements: 0,
+    }
+}
+
+#[test]
+fn test_scheduler() {
+    let config = ScheduleConfig {
+        parallel: 1,
+        ..ScheduleConfig::default()
+    };
+    let mut scheduler = Scheduler {
+        tasks: vec![
+            test_task(1, Some(0.0)),
+            test_task(2, Some(100.0)),
+            test_task(3, Some(30.0)),
+        ],
+    };
+    // dislike 0 の問題は選ばず、残りの得点が多い問題から
+    assert_eq!(scheduler.next(3, &config), vec![1, 2]);
+
+    // 改善しなければ見積もりが下がり、他の問題に回る
+    scheduler.report(1, Some(100.0));
+    scheduler.report(1, Some(100.0));
+    scheduler.report(1, Some(100.0));
+    assert_eq!(scheduler.next(1, &config), vec![2]);
+
+    // 改善すれば dislike を更新する
+    scheduler.report(2, Some(10.0));
+    assert_eq!(scheduler.tasks[2].dislike, Some(10.0));
+    assert_eq!(scheduler.tasks[2].improvements, 1);
+}
+
+// ids の問題に schedule.total の時間を配って solve2 で解く
+// slice ごとに best を読み直して見積もりを更新する
+pub fn solve_scheduled(ids: &[usize], seed: u64, config: &SolverConfig, schedule: &ScheduleConfig) {
+    let tasks = ids
+        .iter()
+        .filter(|&&id| config.data.has_problem(id))
+        .map(|&id| {
+            let problem = Problem::from_file(config.data.problem(id).as_str());
+            let dislike = best_dislike(&config.data, &problem, id);
+            Task {
+                id,
+                problem,
+                dislike,
+                runs: 0,
+                improvements: 0,
+            }
+        })
+        .collect::<Vec<Task>>();
+    let mut scheduler = Scheduler { tasks };
+
+    let timer = Instant::now();
+    let mut round = 0;
+    while timer.elapsed().as_millis() + schedule.slice <= schedule.total {
+        let picked = scheduler.next(schedule.parallel, schedule);
+        if picked.is_empty() {
+            println!("schedule: no problem left to improve");
+            break;
+        }
+        for &i in picked.iter() {
+            let task = &scheduler.tasks[i];
+            println!(
+                "schedule round {}: problem {} (dislike {:?}, expected {:.1} points/s)",
+                round,
+                task.id,
+                task.dislike,
+                task.expected_gain_rate(schedule)
+            );
+        }
+
+        // 同じ round の問題は並列に解く。round ごとに seed をずらす
+        let round_seed = seed.wrapping_add(round);
+        let results = picked
+            .par_iter()
+            .map(|&i| {
+                let task = &scheduler.tasks[i];
+                solve2(
+                    &task.problem,
+                    round_seed,
+                    Budget::Time(schedule.slice),
+                    task.id,
+                    config,
+                );
+                (i, best_dislike(&config.data, &task.problem, task.id))
+            })
+            .collect::<Vec<(usize, Option<f64>)>>();
+        for (i, dislike) in results {
+            scheduler.report(i, dislike);
+        }
+        round += 1;
+    }
+
+    for task in scheduler.tasks.iter().filter(|t| t.runs > 0) {
+        println!(
+            "problem {}: {} runs, {} improvements, dislike {:?}",
+            task.id, task.runs, task.improvements, task.dislike
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use crate::neighborhood::{Move, MOVES};
+use crate::SolverConfig;
+use lib::paths::create_parent;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::time::Instant;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TelemetryFormat {
+    Jsonl,
+    Csv,
+}
+
+impl TelemetryFormat {
+    pub fn from_name(name: &str) -> Result<TelemetryFormat, String> {
+        match name {
+            "jsonl" => Ok(TelemetryFormat::Jsonl),
+            "csv" => Ok(TelemetryFormat::Csv),
+            _ => Err(format!("unknown telemetry format {}", name)),
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            TelemetryFormat::Jsonl => "jsonl",
+            TelemetryFormat::Csv => "csv",
+        }
+    }
+}
+
+// solve2 の焼きなましの数値を一定の間隔で 1 行ずつ書き出す
+// 同じファイルに追記するので、seed や設定を変えた実行を並べて比べられる
+#[derive(Clone, Debug)]
+pub struct TelemetryConfig {
+    // 何ミリ秒ごとに書くか
+    pub interval: u128,
+    pub format: TelemetryFormat,
+    // None なら data の debug/telemetry_{id}.{jsonl,csv}
+    pub filepath: Option<String>,
+    // 行に付ける設定の名前。None なら近傍の重み
+    pub label: Option<String>,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> TelemetryConfig {
+        TelemetryConfig {
+            interval: 1000,
+            format: TelemetryFormat::Jsonl,
+            filepath: None,
+            label: None,
+        }
+    }
+}
+
+// 1 行分の値
+struct Row {
+    chain: usize,
+    iteration: usize,
+    elapsed: u128,
+    iterations_per_sec: f64,
+    temperature: f64,
+    current_eval: f64,
+    best_eval: f64,
+    penalty: (f64, f64, f64),
+    // 近傍の種類ごとの受理率。試していなければ None
+    acceptance: Vec<Option<f64>>,
+}
+
+fn csv_header() -> String {
+    let mut header = "problem,seed,config,chain,iteration,elapsed_ms,iterations_per_sec,temperature,current_eval,best_eval,p0,p1,p2".to_string();
+    for m in MOVES.iter() {
+        header += format!(",accept_{}", m.name()).as_str();
+    }
+    header
+}
+
+pub struct Telemetry {
+    file: File,
+    filepath: String,
+    format: TelemetryFormat,
+    interval: u128,
+    problem_id: usize,
+    seed: u64,
+    label: String,
+    timer: Instant,
+    last_time: u128,
+    last_iteration: usize,
+    chain: usize,
+    // 前の行からの、近傍の種類ごとの (試した回数, 受理した回数)
+    moves: [(usize, usize); 8],
+}
+
+impl Telemetry {
+    // config.telemetry が指定されていなければ書かない
+    pub fn new(config: &SolverConfig, problem_id: usize, seed: u64) -> Option<Telemetry> {
+        let telemetry = config.telemetry.as_ref()?;
+        let filepath = match &telemetry.filepath {
+            Some(filepath) => filepath.clone(),
+            None => config.data.debug(
+                format!("telemetry_{}.{}", problem_id, telemetry.format.extension()).as_str(),
+            ),
+        };
+        let mut file = match create_parent(filepath.as_str()).and_then(|_| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(filepath.as_str())
+                .map_err(|e| e.to_string())
+        }) {
+            Ok(file) => file,
+            Err(e) => {
+                println!("telemetry: cannot open {}: {}", filepath, e);
+                return None;
+            }
+        };
+        let is_empty = file.metadata().map(|m| m.len() == 0).unwrap_or(false);
+        if telemetry.format == TelemetryFormat::Csv && is_empty {
+            writeln!(file, "{}", csv_header()).unwrap();
+        }
+        let label = match &telemetry.label {
+            Some(label) => label.clone(),
+            None => config.neighborhood.summary(),
+        };
+        Some(Telemetry {
+            file,
+            filepath,
+            format: telemetry.format,
+            interval: telemetry.interval.max(1),
+            problem_id,
+            seed,
+            label,
+            timer: Instant::now(),
+            last_time: 0,
+            last_iteration: 0,
+            chain: 0,
+            moves: [(0, 0); 8],
+        })
+    }
+
+    pub fn count(&mut self, kind: Move, accepted: bool) {
+        let entry = &mut self.moves[kind.index()];
+        entry.0 += 1;
+        if accepted {
+            entry.1 += 1;
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.timer.elapsed().as_millis() >= self.last_time + self.interval
+    }
+
+    // penalty は今の解の penalty()
+    pub fn write(
+        &mut self,
+        iteration: usize,
+        temperature: f64,
+        current_eval: f64,
+        best_eval: f64,
+        penalty: (f64, f64, f64),
+    ) {
+        let elapsed = self.timer.elapsed().as_millis();
+        let seconds = (elapsed - self.last_time) as f64 / 1000.0;
+        let iterations = iteration - self.last_iteration;
+        let row = Row {
+            chain: self.chain,
+            iteration,
+            elapsed,
+            iterations_per_sec: if seconds > 0.0 {
+                iterations as f64 / seconds
+            } else {
+                0.0
+            },
+            temperature,
+            current_eval,
+            best_eval,
+            penalty,
+            acceptance: self
+                .moves
+                .iter()
+                .map(|&(tried, accepted)| {
+                    if tried > 0 {
+                        Some(accepted as f64 / tried as f64)
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+        };
+        let line = match self.format {
+            TelemetryFormat::Jsonl => self.to_json(&row),
+            TelemetryFormat::Csv => self.to_csv(&row),
+        };
+        // 並列に解いている他のプロセスと行が混ざらないよう、1 行ずつ書く
+        self.file
+            .write_all(format!("{}\n", line).as_bytes())
+            .unwrap();
+        self.last_time = elapsed;
+        self.last_iteration = iteration;
+        self.moves = [(0, 0); 8];
+    }
+
+    // 次の初期解からの焼きなましに移る
+    pub fn next_chain(&mut self) {
+        self.chain += 1;
+        self.last_iteration = 0;
+    }
+
+    pub fn finish(&self) {
+        println!("telemetry: append to {}", self.filepath);
+    }
+
+    fn to_json(&self, row: &Row) -> String {
+        let mut line = format!(
+            "{{\"problem\": {}, \"seed\": {}, \"config\": \"{}\", \"chain\": {}, \"iteration\": {}, \"elapsed_ms\": {}, \"iterations_per_sec\": {}, \"temperature\": {}, \"current_eval\": {}, \"best_eval\": {}, \"penalty\": [{}, {}, {}], \"acceptance\": {{",
+            self.problem_id,
+            self.seed,
+            self.label.replace('\\', "\\\\").replace('"', "\\\""),
+            row.chain,
+            row.iteration,
+            row.elapsed,
+            row.iterations_per_sec,
+            row.temperature,
+            row.current_eval,
+            row.best_eval,
+            row.penalty.0,
+            row.penalty.1,
+            row.penalty.2
+        );
+        let acceptance = MOVES
+            .iter()
+            .zip(row.acceptance.iter())
+            .map(|(m, rate)| match rate {
+                Some(rate) => format!("\"{}\": {}", m.name(), rate),
+                None => format!("\"{}\": null", m.name()),
+            })
+            .collect::<Vec<String>>();
+        line += acceptance.join(", ").as_str();
+        line += "}}";
+        line
+    }
+
+    fn to_csv(&self, row: &Row) -> String {
+        let mut line = format!(
+            "{},{},\"{}\",{},{},{},{},{},{},{},{},{},{}",
+            self.problem_id,
+            self.seed,
+            self.label.replace('"', "\"\""),
+            row.chain,
+            row.iteration,
+            row.elapsed,
+            row.iterations_per_sec,
+            row.temperature,
+            row.current_eval,
+            row.best_eval,
+            row.penalty.0,
+            row.penalty.1,
+            row.penalty.2
+        );
+        for rate in row.acceptance.iter() {
+            line += ",";
+            if let Some(rate) = rate {
+                line += rate.to_string().as_str();
+            }
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+fn test_telemetry(format: TelemetryFormat) -> Telemetry {
+    let filepath = std::env::temp_dir().join(format!(
+        "telemetry_test_{}_{}",
+        std::process::id(),
+        format.extension()
+    ));
+    let config = SolverConfig {
+        telemetry: Some(TelemetryConfig {
+            format,
+            filepath: Some(filepath.to_str().unwrap().to_string()),
+            label: Some("a \"b\"".to_string()),
+            ..TelemetryConfig::default()
+        }),
+        ..SolverConfig::default()
+    };
+    Telemetry::new(&config, 3, 7).unwrap()
+}
+
+#[test]
+fn test_telemetry_rows() {
+    let row = Row {
+        chain: 1,
+        iteration: 2048,
+        elapsed: 1000,
+        iterations_per_sec: 2048.0,
+        temperature: 1.5,
+        current_eval: 0.5,
+        best_eval: 0.25,
+        penalty: (1.0, 0.0, 2.0),
+        acceptance: vec![Some(0.5), None, None, None, None, Some(1.0), None, None],
+    };
+
+    let telemetry = test_telemetry(TelemetryFormat::Jsonl);
+    let json = telemetry.to_json(&row);
+    assert!(json.starts_with(
+        "{\"problem\": 3, \"seed\": 7, \"config\": \"a \\\"b\\\"\", \"chain\": 1, \"iteration\": 2048,"
+    ));
+    assert!(json.contains("\"penalty\": [1, 0, 2]"));
+    assert!(
+        json.ends_with("\"ring\": 1, \"pendant_rotate\": null, \"articulation_reflect\": null}}")
+    );
+    std::fs::remove_file(telemetry.filepath.as_str()).unwrap();
+
+    let telemetry = test_telemetry(TelemetryFormat::Csv);
+    let csv = telemetry.to_csv(&row);
+    assert_eq!(
+        csv,
+        "3,7,\"a \"\"b\"\"\",1,2048,1000,2048,1.5,0.5,0.25,1,0,2,0.5,,,,,1,,"
+    );
+    // 新しいファイルにはヘッダを書く
+    let written = std::fs::read_to_string(telemetry.filepath.as_str()).unwrap();
+    assert_eq!(written, csv_header() + "\n");
+    assert_eq!(csv_header().split(',').count(), csv.split(',').count());
+    std::fs::remove_file(telemetry.filepath.as_str()).unwrap();
+}
@@ -0,0 +1,292 @@
+use crate::neighborhood::{Neighborhood, NeighborhoodConfig};
+use crate::{
+    dislike, evaluate_with_validity, initial_solutions, penalty, save_pose, save_to_best, Budget,
+    Solution, SolverConfig, SolverProblem,
+};
+use lib::data::{Pose, Problem};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::time::Instant;
+
+// 1 問を複数の温度のレプリカで並列に焼きなます (replica exchange)
+#[derive(Clone, Debug)]
+pub struct TemperingConfig {
+    pub replica_count: usize,
+    pub min_temperature: f64,
+    pub max_temperature: f64,
+    // 交換を試みるまでに、各レプリカが回す反復回数
+    pub exchange_interval: usize,
+}
+
+impl Default for TemperingConfig {
+    fn default() -> TemperingConfig {
+        TemperingConfig {
+            replica_count: rayon::current_num_threads().max(2),
+            min_temperature: 0.1,
+            max_temperature: 3.0,
+            exchange_interval: 4096,
+        }
+    }
+}
+
+impl TemperingConfig {
+    // 温度は等比数列で、index が小さいほど低温
+    fn temperature(&self, index: usize) -> f64 {
+        if self.replica_count <= 1 {
+            return self.min_temperature;
+        }
+        let rate = index as f64 / (self.replica_count - 1) as f64;
+        self.min_temperature * (self.max_temperature / self.min_temperature).powf(rate)
+    }
+}
+
+struct Replica {
+    temperature: f64,
+    solution: Solution,
+    eval: f64,
+    // このレプリカで見つけた最良の valid な解
+    best_valid: Option<(f64, Solution)>,
+    counter: usize,
+    // スレッドの割り当てに依らず再現できるように、レプリカごとに持つ
+    rng: StdRng,
+}
+
+impl Replica {
+    fn run(
+        &mut self,
+        iterations: usize,
+        problem: &SolverProblem,
+        neighborhood: &Neighborhood,
+        config: &NeighborhoodConfig,
+        epsilon: f64,
+    ) {
+        for _ in 0..iterations {
+            if let Some(undo) =
+                neighborhood.apply(config, problem, &mut self.solution, &mut self.rng)
+            {
+                let (after_eval, is_valid) =
+                    evaluate_with_validity(problem, &self.solution, epsilon);
+                let de = after_eval - self.eval;
+
+                if de < 0.0 || self.rng.gen::<f64>() < (-de / self.temperature).exp() {
+                    self.eval = after_eval;
+                    let improved = match &self.best_valid {
+                        Some((best_eval, _)) => after_eval < *best_eval,
+                        None => true,
+                    };
+                    if is_valid && improved {
+                        self.best_valid = Some((after_eval, self.solution.clone()));
+                    }
+                } else {
+                    undo.rollback(&mut self.solution);
+                }
+            }
+        }
+        self.counter += iterations;
+    }
+}
+
+// 交換を繰り返した後の状態。交換の回数は、隣り合う温度のレプリカの組ごとに数える
+struct Tempering {
+    replicas: Vec<Replica>,
+    // 全レプリカで共有する最良の valid な解
+    best_valid: Option<(f64, Solution)>,
+    exchange_tried: Vec<usize>,
+    exchange_accepted: Vec<usize>,
+}
+
+// 初期解が複数あれば、レプリカごとに順番に割り当てる
+fn temper(
+    problem: &SolverProblem,
+    config: &SolverConfig,
+    inits: &[Solution],
+    epsilon: f64,
+    budget: Budget,
+    tempering: &TemperingConfig,
+    rng: &mut StdRng,
+) -> Tempering {
+    let neighborhood = Neighborhood::new(problem, problem.ring_epsilon(epsilon));
+    let replica_count = tempering.replica_count.max(1);
+    let replicas = (0..replica_count)
+        .map(|i| Replica {
+            temperature: tempering.temperature(i),
+            solution: inits[i % inits.len()].clone(),
+            eval: evaluate_with_validity(problem, &inits[i % inits.len()], epsilon).0,
+            best_valid: None,
+            counter: 0,
+            rng: StdRng::seed_from_u64(rng.gen::<u64>()),
+        })
+        .collect::<Vec<Replica>>();
+    let mut state = Tempering {
+        replicas,
+        best_valid: None,
+        exchange_tried: vec![0; replica_count],
+        exchange_accepted: vec![0; replica_count],
+    };
+    let replicas = &mut state.replicas;
+
+    let timer = Instant::now();
+    let mut round = 0;
+    // 反復回数は各レプリカあたりで数える
+    while budget.progress(replicas[0].counter, &timer).is_some() {
+        let iterations = match budget {
+            Budget::Iterations(max_counter) => tempering
+                .exchange_interval
+                .min(max_counter - replicas[0].counter),
+            Budget::Time(_) => tempering.exchange_interval,
+        };
+        replicas.par_iter_mut().for_each(|replica| {
+            replica.run(
+                iterations,
+                problem,
+                &neighborhood,
+                &config.neighborhood,
+                epsilon,
+            )
+        });
+
+        for replica in replicas.iter() {
+            if let Some((eval, solution)) = &replica.best_valid {
+                let improved = match &state.best_valid {
+                    Some((best_eval, _)) => eval < best_eval,
+                    None => true,
+                };
+                if improved {
+                    state.best_valid = Some((*eval, solution.clone()));
+                }
+            }
+        }
+
+        // 隣り合う温度のレプリカ同士で、Metropolis 基準で状態を交換
+        // 偶数ラウンドは (0, 1), (2, 3), ...、奇数ラウンドは (1, 2), (3, 4), ...
+        let mut i = round % 2;
+        while i + 1 < replica_count {
+            let (low, high) = replicas.split_at_mut(i + 1);
+            let low = &mut low[i];
+            let high = &mut high[0];
+            let delta = (1.0 / low.temperature - 1.0 / high.temperature) * (low.eval - high.eval);
+            state.exchange_tried[i] += 1;
+            if delta >= 0.0 || rng.gen::<f64>() < delta.exp() {
+                std::mem::swap(&mut low.solution, &mut high.solution);
+                std::mem::swap(&mut low.eval, &mut high.eval);
+                state.exchange_accepted[i] += 1;
+            }
+            i += 2;
+        }
+
+        // 最も低温のレプリカは、共有している最良解より悪ければ書き戻し
+        if let Some((eval, solution)) = &state.best_valid {
+            if replicas[0].eval > *eval {
+                replicas[0].solution = solution.clone();
+                replicas[0].eval = *eval;
+            }
+        }
+
+        round += 1;
+    }
+    state
+}
+
+pub fn solve_tempering(
+    _problem: &Problem,
+    seed: u64,
+    budget: Budget,
+    problem_id: usize,
+    config: &SolverConfig,
+    tempering: &TemperingConfig,
+) -> Option<Pose> {
+    let mut problem = SolverProblem::new(_problem);
+    problem.bonus = config.bonus;
+    problem.bonus_weight = config.bonus_weight;
+    let epsilon = _problem.epsilon;
+
+    // 交換の判定と、各レプリカの seed の生成に使う
+    let mut rng = StdRng::seed_from_u64(seed);
+    let inits = initial_solutions(&problem, config, epsilon, problem_id, &mut rng);
+    let Tempering {
+        replicas,
+        best_valid,
+        exchange_tried,
+        exchange_accepted,
+    } = temper(
+        &problem, config, &inits, epsilon, budget, tempering, &mut rng,
+    );
+    let replica_count = replicas.len();
+
+    let counter = replicas.iter().map(|r| r.counter).sum::<usize>();
+    println!("counter = {} ({} replicas)", counter, replica_count);
+    println!("seed = {}", seed);
+    for i in 0..replica_count - 1 {
+        println!(
+            "exchange {} <-> {}: {} / {}",
+            i,
+            i + 1,
+            exchange_accepted[i],
+            exchange_tried[i]
+        );
+    }
+
+    if let Some((_, best_solution)) = best_valid {
+        println!("score: {}", dislike(&problem, &best_solution));
+        let saved = save_to_best(
+            config,
+            _problem,
+            &problem,
+            &best_solution,
+            problem_id,
+            seed,
+            "tempering",
+        );
+        if saved {
+            Some(best_solution.to_pose(&problem))
+        } else {
+            None
+        }
+    } else {
+        // valid な解が見つからなかったので、最も評価値の良い状態を出力
+        let best_replica = (0..replica_count)
+            .min_by(|&a, &b| replicas[a].eval.partial_cmp(&replicas[b].eval).unwrap())
+            .unwrap();
+        let best_solution = &replicas[best_replica].solution;
+        println!("score: {}", dislike(&problem, best_solution));
+        let (p0, p1, p2) = penalty(&problem, best_solution, epsilon);
+        println!("penalty: {} {} {}", p0, p1, p2);
+        save_pose(
+            &best_solution.to_pose(&problem),
+            config.out_filepath(problem_id),
+            seed,
+        );
+        None
+    }
+}
+
+#[test]
+fn test_temper() {
+    use lib::validation::validate;
+
+    let mut problem = crate::bonus_test_problem();
+    problem.epsilon = 0.15;
+    let solver_problem = SolverProblem::new(&problem);
+    let inits = vec![Solution::new(&solver_problem.orig_figure_vertices)];
+    let tempering = TemperingConfig {
+        replica_count: 4,
+        exchange_interval: 256,
+        ..TemperingConfig::default()
+    };
+    let state = temper(
+        &solver_problem,
+        &SolverConfig::default(),
+        &inits,
+        problem.epsilon,
+        Budget::Iterations(4096),
+        &tempering,
+        &mut StdRng::seed_from_u64(1),
+    );
+    assert!(state.replicas.iter().all(|r| r.counter == 4096));
+    // 16 ラウンドで、組 (0, 1) と (2, 3) は偶数ラウンド、(1, 2) は奇数ラウンドに試す
+    assert_eq!(state.exchange_tried, vec![8, 8, 8, 0]);
+    assert!(state.exchange_accepted.iter().sum::<usize>() > 0);
+    let (_, best) = state.best_valid.unwrap();
+    assert!(validate(&problem, &best.to_pose(&solver_problem)).is_valid());
+}
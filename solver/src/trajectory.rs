@@ -0,0 +1,102 @@
+use crate::{dislike, penalty, Solution, SolverConfig, SolverProblem};
+use lib::paths::create_parent;
+use lib::trajectory::{Trajectory, TrajectorySample};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+// solve2 の焼きなましの途中経過を記録する (lib::trajectory の形式)
+#[derive(Clone, Debug)]
+pub struct TrajectoryConfig {
+    // 何回の反復ごとに記録するか
+    pub interval: usize,
+    // None なら data の debug/trajectory_{id}.txt
+    pub filepath: Option<String>,
+}
+
+impl Default for TrajectoryConfig {
+    fn default() -> TrajectoryConfig {
+        TrajectoryConfig {
+            interval: 10000,
+            filepath: None,
+        }
+    }
+}
+
+pub struct Recorder {
+    writer: BufWriter<File>,
+    filepath: String,
+    interval: usize,
+    chain: usize,
+}
+
+impl Recorder {
+    // config.trajectory が指定されていなければ記録しない
+    pub fn new(
+        config: &SolverConfig,
+        problem: &SolverProblem,
+        problem_id: usize,
+        seed: u64,
+    ) -> Option<Recorder> {
+        let trajectory = config.trajectory.as_ref()?;
+        let filepath = match &trajectory.filepath {
+            Some(filepath) => filepath.clone(),
+            None => config
+                .data
+                .debug(format!("trajectory_{}.txt", problem_id).as_str()),
+        };
+        let file = match create_parent(filepath.as_str())
+            .and_then(|_| File::create(filepath.as_str()).map_err(|e| e.to_string()))
+        {
+            Ok(file) => file,
+            Err(e) => {
+                println!("trajectory: cannot create {}: {}", filepath, e);
+                return None;
+            }
+        };
+        let mut writer = BufWriter::new(file);
+        writer
+            .write_all(Trajectory::header(problem_id, seed, problem.bonus.as_ref()).as_bytes())
+            .unwrap();
+        Some(Recorder {
+            writer,
+            filepath,
+            interval: trajectory.interval.max(1),
+            chain: 0,
+        })
+    }
+
+    pub fn is_due(&self, iteration: usize) -> bool {
+        iteration.is_multiple_of(self.interval)
+    }
+
+    pub fn record(
+        &mut self,
+        problem: &SolverProblem,
+        solution: &Solution,
+        epsilon: f64,
+        iteration: usize,
+        temperature: f64,
+        eval: f64,
+    ) {
+        let sample = TrajectorySample {
+            chain: self.chain,
+            iteration,
+            temperature,
+            eval,
+            dislike: dislike(problem, solution),
+            penalty: penalty(problem, solution, epsilon),
+            vertices: solution.to_pose(problem).vertices,
+        };
+        writeln!(self.writer, "{}", sample.to_line()).unwrap();
+    }
+
+    // 次の初期解からの焼きなましに移る
+    pub fn next_chain(&mut self) {
+        self.chain += 1;
+    }
+
+    pub fn finish(mut self) {
+        self.writer.flush().unwrap();
+        println!("trajectory: write {}", self.filepath);
+    }
+}
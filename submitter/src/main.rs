@@ -1,17 +1,29 @@
-use lib::client::submit_problem;
-use lib::data::Pose;
+use lib::client::{submit_problem, ClientConfig};
+use lib::data::{Pose, Problem};
+use lib::paths::DataDir;
+use lib::store::SolutionStore;
+use lib::validation::dislike;
 use rayon::prelude::*;
 
+use std::env;
 use std::path::Path;
 
 fn main() {
     let max_id = 132;
+    let data = DataDir::from_args(&env::args().collect::<Vec<String>>());
+    let client = match ClientConfig::load(&data) {
+        Ok(client) => client,
+        Err(msg) => {
+            println!("{}", msg);
+            std::process::exit(1);
+        }
+    };
 
     let pose_list = (1..=max_id)
         .collect::<Vec<usize>>()
         .par_iter()
         .map(|id| -> Option<Pose> {
-            let best_filepath = format!("data/best/{}.json", id);
+            let best_filepath = data.best(*id);
             if !Path::new(best_filepath.as_str()).exists() {
                 None
             } else {
@@ -27,9 +39,15 @@ fn main() {
         .for_each(|(id, maybe_pose)| {
             let id = id + 1;
             if let Some(pose) = maybe_pose {
-                if let Err(_msg) = submit_problem(id, pose) {
-                    println!("fail to submit problem {}", id);
+                // 受け付けられたときだけ submitted にする
+                if let Err(msg) = submit_problem(&client, id, pose) {
+                    println!("fail to submit problem {}: {}", id, msg);
                 } else {
+                    let problem = Problem::from_file(data.problem(id).as_str());
+                    let store = SolutionStore::new(&data);
+                    if let Err(msg) = store.mark_pose_submitted(id, pose, dislike(&problem, pose)) {
+                        println!("fail to mark problem {} submitted: {}", id, msg);
+                    }
                 }
             }
         });